@@ -0,0 +1,160 @@
+use core::fmt::{self, Display};
+
+/// Declares a struct whose fields are packed MSB-first into whole bytes, with
+/// the width of each field in bits given after `=`.
+///
+/// Fields must be unsigned integers no wider than their type, and the widths
+/// must add up to a multiple of 8, at most 256. The struct serializes as the
+/// packed bytes; serializing a field whose value does not fit in its width is
+/// an error.
+///
+/// ```
+/// use serde_net::{bitfield, from_bytes, to_vec};
+///
+/// bitfield! {
+///     #[derive(Debug, PartialEq)]
+///     pub struct Fragment {
+///         pub flags: u8 = 3,
+///         pub offset: u16 = 13,
+///     }
+/// }
+///
+/// let value = Fragment { flags: 0b010, offset: 185 };
+/// let mut bytes = to_vec(&value).unwrap();
+/// assert_eq!(bytes, [0x40, 0xb9]);
+/// assert_eq!(from_bytes::<_, Fragment>(&mut bytes).unwrap(), value);
+/// ```
+#[macro_export]
+macro_rules! bitfield {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_attr:meta])*
+                $field_vis:vis $field:ident: $ty:ty = $bits:expr
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name {
+            $(
+                $(#[$field_attr])*
+                $field_vis $field: $ty,
+            )*
+        }
+
+        impl $name {
+            /// Width of the packed struct in bits.
+            pub const BITS: u32 = 0 $(+ $bits)*;
+        }
+
+        const _: () = {
+            let _: [(); 0] = [(); ($name::BITS % 8) as usize];
+            let _: [(); 0] = [(); ($name::BITS > 256) as usize];
+            $(
+                let _: [(); 0] = [(); ($bits > 8 * ::core::mem::size_of::<$ty>()) as usize];
+            )*
+        };
+
+        impl $crate::__private::serde::Serialize for $name {
+            #[allow(unused_assignments)]
+            fn serialize<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+            where
+                S: $crate::__private::serde::Serializer,
+            {
+                let mut buf = [0u8; ($name::BITS / 8) as usize];
+                let mut offset = 0;
+                $(
+                    $crate::__private::pack(
+                        &mut buf,
+                        offset,
+                        $bits,
+                        self.$field as u128,
+                        ::core::stringify!($name),
+                        ::core::stringify!($field),
+                    )
+                    .map_err($crate::__private::serde::ser::Error::custom)?;
+                    offset += $bits;
+                )*
+                $crate::__private::serde::Serialize::serialize(&buf, serializer)
+            }
+        }
+
+        impl<'de> $crate::__private::serde::Deserialize<'de> for $name {
+            #[allow(unused_assignments)]
+            fn deserialize<D>(deserializer: D) -> ::core::result::Result<Self, D::Error>
+            where
+                D: $crate::__private::serde::Deserializer<'de>,
+            {
+                let buf: [u8; ($name::BITS / 8) as usize] =
+                    $crate::__private::serde::Deserialize::deserialize(deserializer)?;
+                let mut offset = 0;
+                ::core::result::Result::Ok($name {
+                    $(
+                        $field: {
+                            let value = $crate::__private::unpack(&buf, offset, $bits);
+                            offset += $bits;
+                            value as $ty
+                        },
+                    )*
+                })
+            }
+        }
+    };
+}
+
+/// Value of a bit field that does not fit in its width, reported through
+/// `serde::ser::Error::custom`.
+#[derive(Debug)]
+pub struct Overflow {
+    name: &'static str,
+    field: &'static str,
+    value: u128,
+    bits: usize,
+}
+
+impl Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}: value {} does not fit in {} bits",
+            self.name, self.field, self.value, self.bits
+        )
+    }
+}
+
+/// Writes the low `bits` bits of `value` MSB-first at bit `offset` of `buf`.
+pub fn pack(
+    buf: &mut [u8],
+    offset: usize,
+    bits: usize,
+    value: u128,
+    name: &'static str,
+    field: &'static str,
+) -> Result<(), Overflow> {
+    if bits < 128 && value >> bits != 0 {
+        return Err(Overflow {
+            name,
+            field,
+            value,
+            bits,
+        });
+    }
+    for i in 0..bits {
+        let bit = (value >> (bits - 1 - i)) & 1;
+        let position = offset + i;
+        #[allow(clippy::cast_possible_truncation)]
+        let bit = (bit as u8) << (7 - position % 8);
+        buf[position / 8] |= bit;
+    }
+    Ok(())
+}
+
+/// Reads `bits` bits MSB-first from bit `offset` of `buf`.
+pub fn unpack(buf: &[u8], offset: usize, bits: usize) -> u128 {
+    (0..bits).fold(0, |value, i| {
+        let position = offset + i;
+        let bit = (buf[position / 8] >> (7 - position % 8)) & 1;
+        (value << 1) | u128::from(bit)
+    })
+}
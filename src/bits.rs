@@ -0,0 +1,304 @@
+//! Bit-stream encoding, where values are not aligned to byte boundaries.
+//!
+//! [`BitSerializer`] and [`BitDeserializer`] cover the same data model as
+//! [`Serializer`](crate::Serializer) and [`Deserializer`](crate::Deserializer),
+//! but write each value in exactly as many bits as its type takes, with the
+//! widths of bools, chars, length prefixes and discriminants set by
+//! [`BitOptions`].
+//!
+//! Integers take the full width of their type unless a narrower one is set
+//! per type, such as with [`BitOptions::with_u16_bits`], which then applies
+//! to every `u16`. Signed integers are written in two's complement and sign
+//! extended when read. Floats always take their full width.
+//!
+//! ```
+//! use serde_net::bits::{self, BitOptions};
+//!
+//! let options = BitOptions::new().with_length_bits(4);
+//! let bytes = bits::to_vec(&(true, vec![1u8, 2]), options).unwrap();
+//! assert_eq!(bytes, [0x90, 0x08, 0x10]);
+//! let value: (bool, Vec<u8>) = bits::from_slice(&bytes, options).unwrap();
+//! assert_eq!(value, (true, vec![1, 2]));
+//! ```
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ErrorKind, Result};
+use crate::options::Limits;
+use crate::write::Write;
+
+mod de;
+mod ser;
+
+pub use de::BitDeserializer;
+pub use ser::BitSerializer;
+
+/// Order in which the bits of a value are written, and in which each byte of
+/// the output is filled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BitOrder {
+    /// Most significant bit first, filling each byte from its high bit.
+    MsbFirst,
+    /// Least significant bit first, filling each byte from its low bit.
+    LsbFirst,
+}
+
+/// How the bits after the last value in the final byte are handled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Padding {
+    /// Fill with zeros, which the deserializer checks for.
+    Zeros,
+    /// Fill with ones, which the deserializer checks for.
+    Ones,
+    /// Fail with [`ErrorKind::UnalignedEnd`]
+    /// unless the value ends on a byte boundary.
+    Reject,
+}
+
+/// Encoding options for [`BitSerializer`] and [`BitDeserializer`].
+///
+/// Widths outside the documented range make serializing and deserializing
+/// fail with [`ErrorKind::InvalidBitWidth`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BitOptions {
+    bit_order: BitOrder,
+    padding: Padding,
+    bool_bits: u32,
+    char_bits: u32,
+    length_bits: u32,
+    discriminant_bits: u32,
+    u8_bits: u32,
+    u16_bits: u32,
+    u32_bits: u32,
+    u64_bits: u32,
+    u128_bits: u32,
+    i8_bits: u32,
+    i16_bits: u32,
+    i32_bits: u32,
+    i64_bits: u32,
+    i128_bits: u32,
+    limits: Limits,
+}
+
+/// Returns `bits` if it is a usable width for a value of at most `max` bits.
+fn check_width(bits: u32, max: u32) -> Result<u32> {
+    if bits == 0 || bits > max {
+        Err(ErrorKind::InvalidBitWidth { bits, max }.into())
+    } else {
+        Ok(bits)
+    }
+}
+
+impl BitOptions {
+    #[must_use]
+    pub const fn new() -> Self {
+        BitOptions {
+            bit_order: BitOrder::MsbFirst,
+            padding: Padding::Zeros,
+            bool_bits: 1,
+            char_bits: 32,
+            length_bits: 16,
+            discriminant_bits: 8,
+            u8_bits: 8,
+            u16_bits: 16,
+            u32_bits: 32,
+            u64_bits: 64,
+            u128_bits: 128,
+            i8_bits: 8,
+            i16_bits: 16,
+            i32_bits: 32,
+            i64_bits: 64,
+            i128_bits: 128,
+            limits: Limits::new(),
+        }
+    }
+
+    #[must_use]
+    pub const fn with_bit_order(mut self, bit_order: BitOrder) -> Self {
+        self.bit_order = bit_order;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_padding(mut self, padding: Padding) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Width of bools and option tags, from 1 to 8.
+    #[must_use]
+    pub const fn with_bool_bits(mut self, bits: u32) -> Self {
+        self.bool_bits = bits;
+        self
+    }
+
+    /// Width of chars, from 1 to 32. 21 bits hold every `char`.
+    #[must_use]
+    pub const fn with_char_bits(mut self, bits: u32) -> Self {
+        self.char_bits = bits;
+        self
+    }
+
+    /// Width of the length prefix of strings, bytes, sequences and maps,
+    /// from 1 to 64.
+    #[must_use]
+    pub const fn with_length_bits(mut self, bits: u32) -> Self {
+        self.length_bits = bits;
+        self
+    }
+
+    /// Width of enum variant indices, from 1 to 32.
+    #[must_use]
+    pub const fn with_discriminant_bits(mut self, bits: u32) -> Self {
+        self.discriminant_bits = bits;
+        self
+    }
+
+    /// Limits for the [`BitDeserializer`], which counts sizes in whole
+    /// bytes.
+    #[must_use]
+    pub const fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    #[must_use]
+    pub const fn bit_order(&self) -> BitOrder {
+        self.bit_order
+    }
+
+    #[must_use]
+    pub const fn padding(&self) -> Padding {
+        self.padding
+    }
+
+    #[must_use]
+    pub const fn bool_bits(&self) -> u32 {
+        self.bool_bits
+    }
+
+    pub(crate) fn bool_width(&self) -> Result<u32> {
+        check_width(self.bool_bits, 8)
+    }
+
+    #[must_use]
+    pub const fn char_bits(&self) -> u32 {
+        self.char_bits
+    }
+
+    pub(crate) fn char_width(&self) -> Result<u32> {
+        check_width(self.char_bits, 32)
+    }
+
+    #[must_use]
+    pub const fn length_bits(&self) -> u32 {
+        self.length_bits
+    }
+
+    pub(crate) fn length_width(&self) -> Result<u32> {
+        check_width(self.length_bits, 64)
+    }
+
+    #[must_use]
+    pub const fn discriminant_bits(&self) -> u32 {
+        self.discriminant_bits
+    }
+
+    pub(crate) fn discriminant_width(&self) -> Result<u32> {
+        check_width(self.discriminant_bits, 32)
+    }
+
+    #[must_use]
+    pub const fn limits(&self) -> Limits {
+        self.limits
+    }
+}
+
+macro_rules! int_bits {
+    ($($ty:ident = $max:literal: $field:ident, $with:ident, $width:ident;)*) => {
+        impl BitOptions {$(
+            #[doc = concat!("Width of `", stringify!($ty), "`, from 1 to ", $max, ".")]
+            ///
+            /// Serializing a value that does not fit fails with
+            /// [`ErrorKind::IntegerWidthOverflow`].
+            #[must_use]
+            pub const fn $with(mut self, bits: u32) -> Self {
+                self.$field = bits;
+                self
+            }
+
+            #[must_use]
+            pub const fn $field(&self) -> u32 {
+                self.$field
+            }
+
+            pub(crate) fn $width(&self) -> Result<u32> {
+                check_width(self.$field, $max)
+            }
+        )*}
+    };
+}
+
+int_bits! {
+    u8 = 8: u8_bits, with_u8_bits, u8_width;
+    u16 = 16: u16_bits, with_u16_bits, u16_width;
+    u32 = 32: u32_bits, with_u32_bits, u32_width;
+    u64 = 64: u64_bits, with_u64_bits, u64_width;
+    u128 = 128: u128_bits, with_u128_bits, u128_width;
+    i8 = 8: i8_bits, with_i8_bits, i8_width;
+    i16 = 16: i16_bits, with_i16_bits, i16_width;
+    i32 = 32: i32_bits, with_i32_bits, i32_width;
+    i64 = 64: i64_bits, with_i64_bits, i64_width;
+    i128 = 128: i128_bits, with_i128_bits, i128_width;
+}
+
+impl Default for BitOptions {
+    fn default() -> Self {
+        BitOptions::new()
+    }
+}
+
+/// Largest value that fits in `bits` bits.
+const fn max_value(bits: u32) -> u64 {
+    if bits >= 64 {
+        u64::MAX
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+/// # Errors
+pub fn to_writer<W, T>(writer: W, value: &T, options: BitOptions) -> Result<()>
+where
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    let mut serializer = BitSerializer::new(writer, options);
+    value.serialize(&mut serializer)?;
+    serializer.finish().map(|_| ())
+}
+
+/// # Errors
+#[cfg(feature = "alloc")]
+pub fn to_vec<T>(value: &T, options: BitOptions) -> Result<Vec<u8>>
+where
+    T: ?Sized + Serialize,
+{
+    let mut serializer = BitSerializer::new(Vec::new(), options);
+    value.serialize(&mut serializer)?;
+    serializer.finish()
+}
+
+/// # Errors
+pub fn from_slice<'a, T>(input: &'a [u8], options: BitOptions) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = BitDeserializer::new(input, options);
+    let value = T::deserialize(&mut deserializer).map_err(|err| err.at(deserializer.position()))?;
+    deserializer.end()?;
+    Ok(value)
+}
@@ -0,0 +1,499 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::str;
+
+use serde::de::{self, Visitor};
+
+use super::{max_value, BitOptions, BitOrder, Padding};
+use crate::de::{Decode, Enum, LengthDefined, Usage};
+use crate::error::{Error, ErrorKind, Result, Segment};
+use crate::options::Limits;
+use crate::read::Reference;
+
+/// Deserializer that unpacks values from a stream of bits in a byte slice.
+///
+/// Strings and bytes are borrowed from the slice when they start on a byte
+/// boundary and copied otherwise, which needs the `alloc` feature.
+pub struct BitDeserializer<'de> {
+    input: &'de [u8],
+    options: BitOptions,
+    /// Offset in bits of the next bit to read.
+    bit: u64,
+    #[cfg(feature = "alloc")]
+    scratch: Vec<u8>,
+    usage: Usage,
+}
+
+impl<'de> BitDeserializer<'de> {
+    pub const fn new(input: &'de [u8], options: BitOptions) -> Self {
+        BitDeserializer {
+            input,
+            options,
+            bit: 0,
+            #[cfg(feature = "alloc")]
+            scratch: Vec::new(),
+            usage: Usage::new(),
+        }
+    }
+
+    /// Byte offset of the next bit to read.
+    pub const fn position(&self) -> u64 {
+        self.bit / 8
+    }
+
+    /// Checks the padding after the last value and that no input remains.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ErrorKind::InvalidPadding`] or [`ErrorKind::UnalignedEnd`]
+    /// if the padding does not match the [`Padding`] policy, and
+    /// [`ErrorKind::TrailingBytes`] if whole bytes remain.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn end(&mut self) -> Result<()> {
+        let offset = (self.bit % 8) as u32;
+        if offset > 0 {
+            let start = self.position();
+            let bits = 8 - offset;
+            let padding = self.read_bits(bits)?;
+            let valid = match self.options.padding() {
+                Padding::Zeros => padding == 0,
+                Padding::Ones => padding == max_value(bits),
+                Padding::Reject => return Err(Error::from(ErrorKind::UnalignedEnd).at(start)),
+            };
+            if !valid {
+                return Err(Error::from(ErrorKind::InvalidPadding).at(start));
+            }
+        }
+        if self.remaining_bits() == 0 {
+            Ok(())
+        } else {
+            Err(Error::from(ErrorKind::TrailingBytes).at(self.position()))
+        }
+    }
+
+    fn remaining_bits(&self) -> u64 {
+        (self.input.len() as u64 * 8).saturating_sub(self.bit)
+    }
+
+    fn eof(&self) -> Error {
+        Error::from(ErrorKind::EofWhileDeserializing).at(self.position())
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn read_bit(&mut self) -> Result<u64> {
+        match self.options.limits().max_size() {
+            Some(limit) if self.position() >= limit => {
+                return Err(Error::from(ErrorKind::SizeLimitExceeded { limit }).at(self.position()));
+            }
+            _ => {}
+        }
+        let byte = match self.input.get((self.bit / 8) as usize) {
+            Some(&byte) => byte,
+            None => return Err(self.eof()),
+        };
+        let offset = (self.bit % 8) as u32;
+        let bit = match self.options.bit_order() {
+            BitOrder::MsbFirst => byte >> (7 - offset),
+            BitOrder::LsbFirst => byte >> offset,
+        };
+        self.bit += 1;
+        Ok(u64::from(bit & 1))
+    }
+
+    fn read_bits(&mut self, bits: u32) -> Result<u64> {
+        let mut value = 0;
+        for i in 0..bits {
+            let bit = self.read_bit()?;
+            match self.options.bit_order() {
+                BitOrder::MsbFirst => value = (value << 1) | bit,
+                BitOrder::LsbFirst => value |= bit << i,
+            }
+        }
+        Ok(value)
+    }
+
+    /// Reads `bits` bits, which may be more than 64.
+    fn read_wide(&mut self, bits: u32) -> Result<u128> {
+        if bits <= 64 {
+            return self.read_bits(bits).map(u128::from);
+        }
+        Ok(match self.options.bit_order() {
+            BitOrder::MsbFirst => {
+                let high = u128::from(self.read_bits(bits - 64)?);
+                (high << 64) | u128::from(self.read_bits(64)?)
+            }
+            BitOrder::LsbFirst => {
+                let low = u128::from(self.read_bits(64)?);
+                (u128::from(self.read_bits(bits - 64)?) << 64) | low
+            }
+        })
+    }
+
+    /// Reads a two's complement value of `bits` bits and sign extends it.
+    #[allow(clippy::cast_possible_wrap)]
+    fn read_signed(&mut self, bits: u32) -> Result<i128> {
+        let shift = 128 - bits;
+        Ok(((self.read_wide(bits)? as i128) << shift) >> shift)
+    }
+
+    fn deserialize_tag(&mut self) -> Result<bool> {
+        let start = self.position();
+        match self.read_bits(self.options.bool_width()?)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            #[allow(clippy::cast_possible_truncation)]
+            tag => Err(Error::from(ErrorKind::InvalidTag(tag as u8)).at(start)),
+        }
+    }
+
+    fn deserialize_length(&mut self) -> Result<usize> {
+        let start = self.position();
+        let length = self.read_bits(self.options.length_width()?)?;
+        usize::try_from(length).map_err(|_| {
+            Error::from(ErrorKind::LengthOverflow {
+                length,
+                max: usize::MAX as u64,
+            })
+            .at(start)
+        })
+    }
+
+    fn deserialize_length_prefixed(&mut self) -> Result<Reference<'de, '_, [u8]>> {
+        let length = self.deserialize_length()?;
+        self.check_alloc(length)?;
+        if length as u64 > self.remaining_bits() / 8 {
+            return Err(self.eof());
+        }
+        if self.bit % 8 == 0 {
+            #[allow(clippy::cast_possible_truncation)]
+            let start = (self.bit / 8) as usize;
+            self.bit += length as u64 * 8;
+            return Ok(Reference::Borrowed(&self.input[start..start + length]));
+        }
+        self.read_unaligned(length)
+    }
+
+    #[cfg(feature = "alloc")]
+    #[allow(clippy::cast_possible_truncation)]
+    fn read_unaligned(&mut self, length: usize) -> Result<Reference<'de, '_, [u8]>> {
+        let mut scratch = core::mem::take(&mut self.scratch);
+        scratch.clear();
+        for _ in 0..length {
+            scratch.push(self.read_bits(8)? as u8);
+        }
+        self.scratch = scratch;
+        Ok(Reference::Copied(&self.scratch))
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn read_unaligned(&mut self, _length: usize) -> Result<Reference<'de, '_, [u8]>> {
+        Err(Error::from(ErrorKind::Unsupported("unaligned bytes")).at(self.position()))
+    }
+}
+
+impl<'de> Decode<'de> for BitDeserializer<'de> {
+    fn position(&self) -> u64 {
+        BitDeserializer::position(self)
+    }
+
+    fn limits(&self) -> Limits {
+        self.options.limits()
+    }
+
+    fn usage(&mut self) -> &mut Usage {
+        &mut self.usage
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn deserialize_discriminant(&mut self) -> Result<u32> {
+        self.read_bits(self.options.discriminant_width()?)
+            .map(|index| index as u32)
+    }
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+impl<'de> de::Deserializer<'de> for &mut BitDeserializer<'de> {
+    type Error = Error;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Error::from(ErrorKind::Unsupported("deserialize_any")).at(self.position()))
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let value = self.deserialize_tag()?;
+        visitor.visit_bool(value)
+    }
+
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let bits = self.options.i8_width()?;
+        let value = self.read_signed(bits)?;
+        visitor.visit_i8(value as i8)
+    }
+
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let bits = self.options.i16_width()?;
+        let value = self.read_signed(bits)?;
+        visitor.visit_i16(value as i16)
+    }
+
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let bits = self.options.i32_width()?;
+        let value = self.read_signed(bits)?;
+        visitor.visit_i32(value as i32)
+    }
+
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let bits = self.options.i64_width()?;
+        let value = self.read_signed(bits)?;
+        visitor.visit_i64(value as i64)
+    }
+
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let bits = self.options.i128_width()?;
+        let value = self.read_signed(bits)?;
+        visitor.visit_i128(value)
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let bits = self.options.u8_width()?;
+        let value = self.read_wide(bits)?;
+        visitor.visit_u8(value as u8)
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let bits = self.options.u16_width()?;
+        let value = self.read_wide(bits)?;
+        visitor.visit_u16(value as u16)
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let bits = self.options.u32_width()?;
+        let value = self.read_wide(bits)?;
+        visitor.visit_u32(value as u32)
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let bits = self.options.u64_width()?;
+        let value = self.read_wide(bits)?;
+        visitor.visit_u64(value as u64)
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let bits = self.options.u128_width()?;
+        let value = self.read_wide(bits)?;
+        visitor.visit_u128(value)
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let value = self.read_bits(32)?;
+        visitor.visit_f32(f32::from_bits(value as u32))
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let value = self.read_bits(64)?;
+        visitor.visit_f64(f64::from_bits(value))
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let start = self.position();
+        let value = self.read_bits(self.options.char_width()?)?;
+        let c = char::from_u32(value as u32)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidChar).at(start))?;
+        visitor.visit_char(c)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let start = self.position();
+        let invalid = |_| Error::from(ErrorKind::InvalidString).at(start);
+        match self.deserialize_length_prefixed()? {
+            Reference::Borrowed(bytes) => {
+                visitor.visit_borrowed_str(str::from_utf8(bytes).map_err(invalid)?)
+            }
+            Reference::Copied(bytes) => visitor.visit_str(str::from_utf8(bytes).map_err(invalid)?),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.deserialize_length_prefixed()? {
+            Reference::Borrowed(bytes) => visitor.visit_borrowed_bytes(bytes),
+            Reference::Copied(bytes) => visitor.visit_bytes(bytes),
+        }
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.deserialize_tag()? {
+            self.nested(|de| visitor.visit_some(de))
+        } else {
+            visitor.visit_none()
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.nested(|de| visitor.visit_newtype_struct(de))
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let length = self.deserialize_length()?;
+        self.nested(|de| visitor.visit_seq(LengthDefined::new(de, length, None)))
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.nested(|de| visitor.visit_seq(LengthDefined::new(de, len, None)))
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+            .map_err(|err| err.in_segment(Segment::Type(name)))
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let length = self.deserialize_length()?;
+        self.nested(|de| visitor.visit_map(LengthDefined::new(de, length, None)))
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.nested(|de| visitor.visit_seq(LengthDefined::new(de, fields.len(), Some(fields))))
+            .map_err(|err| err.in_segment(Segment::Type(name)))
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.nested(|de| visitor.visit_enum(Enum::new(de, variants)))
+            .map_err(|err| err.in_segment(Segment::Type(name)))
+    }
+
+    fn deserialize_identifier<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Error::from(ErrorKind::Unsupported("deserialize_identifier")).at(self.position()))
+    }
+
+    fn deserialize_ignored_any<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Error::from(ErrorKind::Unsupported("deserialize_ignored_any")).at(self.position()))
+    }
+}
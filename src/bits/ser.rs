@@ -0,0 +1,368 @@
+use serde::{ser, Serialize};
+
+use super::{max_value, BitOptions, BitOrder, Padding};
+use crate::error::{Error, ErrorKind, Result, Segment};
+use crate::ser::Compound;
+#[cfg(not(feature = "alloc"))]
+use crate::ser::{collect_str_unbuffered, WriteStr};
+use crate::write::Write;
+
+/// Serializer that packs values into a stream of bits.
+///
+/// Call [`finish`](BitSerializer::finish) once done to write the final
+/// partial byte.
+pub struct BitSerializer<W: Write> {
+    output: W,
+    options: BitOptions,
+    /// Bits of the current byte that have not been written yet.
+    byte: u8,
+    filled: u32,
+}
+
+impl<W: Write> BitSerializer<W> {
+    pub const fn new(writer: W, options: BitOptions) -> Self {
+        BitSerializer {
+            output: writer,
+            options,
+            byte: 0,
+            filled: 0,
+        }
+    }
+
+    /// Pads and writes the final partial byte, then returns the writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ErrorKind::UnalignedEnd`] if the output does not end on a
+    /// byte boundary and the padding is [`Padding::Reject`].
+    pub fn finish(mut self) -> Result<W> {
+        if self.filled > 0 {
+            match self.options.padding() {
+                Padding::Zeros => self.write_bits(0, 8 - self.filled)?,
+                Padding::Ones => self.write_bits(u64::MAX, 8 - self.filled)?,
+                Padding::Reject => return Err(ErrorKind::UnalignedEnd.into()),
+            }
+        }
+        Ok(self.output)
+    }
+
+    fn write_bit(&mut self, bit: u64) -> Result<()> {
+        #[allow(clippy::cast_possible_truncation)]
+        let bit = (bit & 1) as u8;
+        match self.options.bit_order() {
+            BitOrder::MsbFirst => self.byte |= bit << (7 - self.filled),
+            BitOrder::LsbFirst => self.byte |= bit << self.filled,
+        }
+        self.filled += 1;
+        if self.filled == 8 {
+            self.output.write_all(&[self.byte])?;
+            self.byte = 0;
+            self.filled = 0;
+        }
+        Ok(())
+    }
+
+    /// Writes the low `bits` bits of `value`.
+    fn write_bits(&mut self, value: u64, bits: u32) -> Result<()> {
+        match self.options.bit_order() {
+            BitOrder::MsbFirst => {
+                for i in (0..bits).rev() {
+                    self.write_bit(value >> i)?;
+                }
+            }
+            BitOrder::LsbFirst => {
+                for i in 0..bits {
+                    self.write_bit(value >> i)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the low `bits` bits of `value`, which may be wider than 64.
+    #[allow(clippy::cast_possible_truncation)]
+    fn write_wide(&mut self, value: u128, bits: u32) -> Result<()> {
+        if bits <= 64 {
+            return self.write_bits(value as u64, bits);
+        }
+        let (high, low) = ((value >> 64) as u64, value as u64);
+        match self.options.bit_order() {
+            BitOrder::MsbFirst => {
+                self.write_bits(high, bits - 64)?;
+                self.write_bits(low, 64)
+            }
+            BitOrder::LsbFirst => {
+                self.write_bits(low, 64)?;
+                self.write_bits(high, bits - 64)
+            }
+        }
+    }
+
+    fn serialize_unsigned(&mut self, value: u128, bits: u32, ty: &'static str) -> Result<()> {
+        if bits < 128 && value >> bits != 0 {
+            return Err(ErrorKind::IntegerWidthOverflow { ty, bits }.into());
+        }
+        self.write_wide(value, bits)
+    }
+
+    fn serialize_signed(&mut self, value: i128, bits: u32, ty: &'static str) -> Result<()> {
+        // The bits above the sign bit must all be copies of it.
+        let high = value >> (bits - 1);
+        if high != 0 && high != -1 {
+            return Err(ErrorKind::IntegerWidthOverflow { ty, bits }.into());
+        }
+        self.write_wide(value as u128, bits)
+    }
+
+    fn serialize_length(&mut self, len: usize) -> Result<()> {
+        let bits = self.options.length_width()?;
+        let length = len as u64;
+        let max = max_value(bits);
+        if length > max {
+            return Err(ErrorKind::LengthOverflow { length, max }.into());
+        }
+        self.write_bits(length, bits)
+    }
+
+    fn serialize_discriminant(&mut self, variant_index: u32) -> Result<()> {
+        let bits = self.options.discriminant_width()?;
+        let max = max_value(bits);
+        if u64::from(variant_index) > max {
+            #[allow(clippy::cast_possible_truncation)]
+            let max = max as u32;
+            return Err(ErrorKind::DiscriminantOverflow {
+                index: variant_index,
+                max,
+            }
+            .into());
+        }
+        self.write_bits(variant_index.into(), bits)
+    }
+}
+
+#[cfg(not(feature = "alloc"))]
+impl<W: Write> WriteStr for BitSerializer<W> {
+    fn serialize_length(&mut self, len: usize) -> Result<()> {
+        BitSerializer::serialize_length(self, len)
+    }
+
+    fn write_str_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        for &byte in bytes {
+            self.write_bits(byte.into(), 8)?;
+        }
+        Ok(())
+    }
+}
+
+#[allow(clippy::cast_sign_loss)]
+impl<'a, W: Write> ser::Serializer for &'a mut BitSerializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'a, BitSerializer<W>>;
+    type SerializeTuple = Compound<'a, BitSerializer<W>>;
+    type SerializeTupleStruct = Compound<'a, BitSerializer<W>>;
+    type SerializeTupleVariant = Compound<'a, BitSerializer<W>>;
+    type SerializeMap = Compound<'a, BitSerializer<W>>;
+    type SerializeStruct = Compound<'a, BitSerializer<W>>;
+    type SerializeStructVariant = Compound<'a, BitSerializer<W>>;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        let bits = self.options.bool_width()?;
+        self.write_bits(v.into(), bits)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        let bits = self.options.i8_width()?;
+        self.serialize_signed(v.into(), bits, "i8")
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        let bits = self.options.i16_width()?;
+        self.serialize_signed(v.into(), bits, "i16")
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        let bits = self.options.i32_width()?;
+        self.serialize_signed(v.into(), bits, "i32")
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        let bits = self.options.i64_width()?;
+        self.serialize_signed(v.into(), bits, "i64")
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        let bits = self.options.i128_width()?;
+        self.serialize_signed(v, bits, "i128")
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        let bits = self.options.u8_width()?;
+        self.serialize_unsigned(v.into(), bits, "u8")
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        let bits = self.options.u16_width()?;
+        self.serialize_unsigned(v.into(), bits, "u16")
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        let bits = self.options.u32_width()?;
+        self.serialize_unsigned(v.into(), bits, "u32")
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        let bits = self.options.u64_width()?;
+        self.serialize_unsigned(v.into(), bits, "u64")
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        let bits = self.options.u128_width()?;
+        self.serialize_unsigned(v, bits, "u128")
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.write_bits(v.to_bits().into(), 32)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.write_bits(v.to_bits(), 64)
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        let bits = self.options.char_width()?;
+        if u64::from(v) > max_value(bits) {
+            return Err(ErrorKind::InvalidChar.into());
+        }
+        self.write_bits(u64::from(v), bits)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.serialize_length(v.len())?;
+        for &byte in v {
+            self.write_bits(byte.into(), 8)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn collect_str<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + core::fmt::Display,
+    {
+        collect_str_unbuffered(self, value)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.serialize_bool(false)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.serialize_bool(true)?;
+        value.serialize(&mut *self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        self.serialize_discriminant(variant_index)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut *self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.serialize_discriminant(variant_index)?;
+        value.serialize(&mut *self).map_err(|err| {
+            err.in_segment(Segment::Variant(variant))
+                .in_segment(Segment::Type(name))
+        })
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        match len {
+            Some(len) => {
+                self.serialize_length(len)?;
+                Ok(Compound::new(self, None, None))
+            }
+            None => Err(ErrorKind::LengthNotKnown.into()),
+        }
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Ok(Compound::new(self, None, None))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Ok(Compound::new(self, Some(name), None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        self.serialize_struct_variant(name, variant_index, variant, len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
+        self.serialize_seq(len)
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Ok(Compound::new(self, Some(name), None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        self.serialize_discriminant(variant_index)?;
+        Ok(Compound::new(self, Some(name), Some(variant)))
+    }
+}
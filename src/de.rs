@@ -1,46 +1,272 @@
-use std::io::{Cursor, Read};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::str;
+#[cfg(feature = "std")]
+use std::io::{self, Cursor};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, NativeEndian};
+use serde::de::{self, Deserialize, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+
+use crate::error::{Error, ErrorKind, Result, Segment};
+use crate::options::{Discriminant, Endian, IntEncoding, LengthPrefix, Limits, Options};
+use crate::quic;
+#[cfg(feature = "std")]
+use crate::read::IoRead;
+use crate::read::{Read, Reference, Scratch, SliceRead};
+use crate::varint;
+
+pub struct Deserializer<R> {
+    input: R,
+    options: Options,
+    scratch: Scratch,
+    usage: Usage,
+}
 
-use byteorder::{NetworkEndian, ReadBytesExt};
-use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+macro_rules! read_endian {
+    ($($method:ident: $ty:ty, $size:expr;)*) => {$(
+        fn $method(&mut self) -> Result<$ty> {
+            let mut buf = [0; $size];
+            self.read_exact(&mut buf)?;
+            Ok(match self.options.endian() {
+                Endian::Big => BigEndian::$method(&buf),
+                Endian::Little => LittleEndian::$method(&buf),
+                Endian::Native => NativeEndian::$method(&buf),
+            })
+        }
+    )*};
+}
 
-use crate::error::{Error, Result};
+impl<'de, R: Read<'de>> Deserializer<R> {
+    read_endian! {
+        read_i16: i16, 2;
+        read_i32: i32, 4;
+        read_i64: i64, 8;
+        read_u16: u16, 2;
+        read_u32: u32, 4;
+        read_u64: u64, 8;
+        read_i128: i128, 16;
+        read_u128: u128, 16;
+        read_f32: f32, 4;
+        read_f64: f64, 8;
+    }
 
-pub struct Deserializer<R: Read> {
-    input: R,
-}
+    fn read_i8(&mut self) -> Result<i8> {
+        self.read_u8().map(|v| v as i8)
+    }
 
-impl<R: Read> Deserializer<R> {
-    fn end(&mut self) -> Result<()> {
+    fn read_u8(&mut self) -> Result<u8> {
         let mut buf = [0; 1];
-        let read_bytes = self.input.read(&mut buf).map_err(Error::io)?;
-        if read_bytes > 0 {
-            Err(Error::TrailingBytes)
-        } else {
-            Ok(())
+        self.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.check_size(buf.len())?;
+        self.input.read_exact(buf)
+    }
+
+    fn check_size(&self, len: usize) -> Result<()> {
+        match self.options.limits().max_size() {
+            Some(limit) if self.position().saturating_add(len as u64) > limit => {
+                Err(Error::from(ErrorKind::SizeLimitExceeded { limit }).at(self.position()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn read_varint(&mut self, bits: u32) -> Result<u128> {
+        let start = self.position();
+        varint::decode(|| self.read_u8(), bits).map_err(|err| err.at(start))
+    }
+
+    fn deserialize_length(&mut self) -> Result<usize> {
+        let start = self.position();
+        let length = match self.options.length_prefix() {
+            LengthPrefix::U8 => self.read_u8().map(u64::from),
+            LengthPrefix::U16 => self.read_u16().map(u64::from),
+            LengthPrefix::U32 => self.read_u32().map(u64::from),
+            LengthPrefix::U64 => self.read_u64(),
+            LengthPrefix::Varint => self.read_varint(64).map(|v| v as u64),
+            LengthPrefix::QuicVarInt => {
+                let first = self.read_u8()?;
+                quic::decode(first, || self.read_u8())
+            }
+        }?;
+        usize::try_from(length).map_err(|_| {
+            Error::from(ErrorKind::LengthOverflow {
+                length,
+                max: usize::MAX as u64,
+            })
+            .at(start)
+        })
+    }
+
+    fn deserialize_length_prefixed(&mut self) -> Result<Reference<'de, '_, [u8]>> {
+        let length = self.deserialize_length()?;
+        self.check_alloc(length)?;
+        self.check_size(length)?;
+        self.input.read_bytes(length, &mut self.scratch)
+    }
+
+    /// Checks that the input has been fully consumed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ErrorKind::TrailingBytes`] if any input remains.
+    pub fn end(&mut self) -> Result<()> {
+        self.input.end()
+    }
+
+    /// Number of bytes consumed from the input so far.
+    pub fn position(&self) -> u64 {
+        self.input.position()
+    }
+
+    fn unsupported(&self, method: &'static str) -> Error {
+        Error::from(ErrorKind::Unsupported(method)).at(self.position())
+    }
+
+    fn deserialize_tag(&mut self) -> Result<bool> {
+        let start = self.position();
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            tag if self.options.strict() => Err(Error::from(ErrorKind::InvalidTag(tag)).at(start)),
+            _ => Ok(true),
         }
     }
 
+    fn deserialize_discriminant(&mut self) -> Result<u32> {
+        match self.options.discriminant() {
+            Discriminant::U8 => self.read_u8().map(u32::from),
+            Discriminant::U16 => self.read_u16().map(u32::from),
+            Discriminant::U32 => self.read_u32(),
+            Discriminant::Varint => self.read_varint(32).map(|v| v as u32),
+        }
+    }
+}
+
+impl<R> Deserializer<R> {
+    const fn with_read(input: R, options: Options) -> Self {
+        Deserializer {
+            input,
+            options,
+            #[cfg(feature = "alloc")]
+            scratch: Vec::new(),
+            #[cfg(not(feature = "alloc"))]
+            scratch: (),
+            usage: Usage::new(),
+        }
+    }
+
+    /// Returns the underlying input source, positioned just after the last
+    /// value read.
+    pub fn into_inner(self) -> R {
+        self.input
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: io::Read> Deserializer<IoRead<R>> {
     pub const fn from_reader(input: R) -> Self {
-        Deserializer { input }
+        Deserializer::from_reader_with_options(input, Options::new())
+    }
+
+    pub const fn from_reader_with_options(input: R, options: Options) -> Self {
+        Deserializer::with_read(IoRead::new(input), options)
     }
 }
 
-impl<T: AsRef<[u8]>> Deserializer<Cursor<T>> {
+#[cfg(feature = "std")]
+impl<T: AsRef<[u8]>> Deserializer<IoRead<Cursor<T>>> {
     pub fn from_bytes(input: T) -> Self {
-        let cursor = Cursor::new(input);
-        Deserializer { input: cursor }
+        Deserializer::from_bytes_with_options(input, Options::new())
+    }
+
+    pub fn from_bytes_with_options(input: T, options: Options) -> Self {
+        Deserializer::from_reader_with_options(Cursor::new(input), options)
+    }
+}
+
+impl<'a> Deserializer<SliceRead<'a>> {
+    pub const fn from_slice(input: &'a [u8]) -> Self {
+        Deserializer::from_slice_with_options(input, Options::new())
+    }
+
+    pub const fn from_slice_with_options(input: &'a [u8], options: Options) -> Self {
+        Deserializer::with_read(SliceRead::new(input), options)
+    }
+}
+
+/// Iterator over values encoded back to back in the input, with no framing
+/// between them.
+///
+/// Iteration ends when the input ends at a value boundary; input that ends
+/// part-way through a value yields [`ErrorKind::EofWhileDeserializing`]. After
+/// an error the iterator yields `None`. Limits apply to the input as a whole,
+/// not to each value.
+pub struct StreamDeserializer<R, T> {
+    de: Deserializer<R>,
+    failed: bool,
+    output: PhantomData<T>,
+}
+
+impl<R, T> StreamDeserializer<R, T> {
+    pub const fn new(de: Deserializer<R>) -> Self {
+        StreamDeserializer {
+            de,
+            failed: false,
+            output: PhantomData,
+        }
+    }
+
+    pub fn into_inner(self) -> Deserializer<R> {
+        self.de
+    }
+}
+
+impl<'de, R, T> Iterator for StreamDeserializer<R, T>
+where
+    R: Read<'de>,
+    T: Deserialize<'de>,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.failed {
+            return None;
+        }
+        let result = match self.de.input.is_eof() {
+            Ok(true) => return None,
+            Ok(false) => T::deserialize(&mut self.de).map_err(|err| err.at(self.de.position())),
+            Err(err) => Err(err),
+        };
+        self.failed = result.is_err();
+        Some(result)
     }
 }
 
 /// # Errors
+#[cfg(feature = "std")]
 pub fn from_reader<R, D>(input: R) -> Result<D>
 where
-    R: Read,
+    R: io::Read,
     D: DeserializeOwned,
 {
-    let mut deserialized = Deserializer::from_reader(input);
-    let value = D::deserialize(&mut deserialized)?;
-    deserialized.end()?;
+    from_reader_with_options(input, Options::default())
+}
+
+/// # Errors
+#[cfg(feature = "std")]
+pub fn from_reader_with_options<R, D>(input: R, options: Options) -> Result<D>
+where
+    R: io::Read,
+    D: DeserializeOwned,
+{
+    let mut deserializer = Deserializer::from_reader_with_options(input, options);
+    let value = D::deserialize(&mut deserializer).map_err(|err| err.at(deserializer.position()))?;
+    deserializer.end()?;
     Ok(value)
 }
 
@@ -50,35 +276,106 @@ where
     T: AsRef<[u8]>,
     D: DeserializeOwned,
 {
-    let mut deserializer = Deserializer::from_bytes(input);
-    let value = D::deserialize(&mut deserializer)?;
+    from_bytes_with_options(input, Options::default())
+}
+
+/// # Errors
+pub fn from_bytes_with_options<T, D>(input: &mut T, options: Options) -> Result<D>
+where
+    T: AsRef<[u8]>,
+    D: DeserializeOwned,
+{
+    from_slice_with_options(input.as_ref(), options)
+}
+
+/// # Errors
+pub fn from_slice<'a, T>(input: &'a [u8]) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    from_slice_with_options(input, Options::default())
+}
+
+/// # Errors
+pub fn from_slice_with_options<'a, T>(input: &'a [u8], options: Options) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::from_slice_with_options(input, options);
+    let value = T::deserialize(&mut deserializer).map_err(|err| err.at(deserializer.position()))?;
     deserializer.end()?;
     Ok(value)
 }
 
-impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
+/// Decodes one value from the front of `input` and returns it with the bytes
+/// that follow it.
+///
+/// # Errors
+pub fn take_from_bytes<D>(input: &[u8]) -> Result<(D, &[u8])>
+where
+    D: DeserializeOwned,
+{
+    take_from_bytes_with_options(input, Options::default())
+}
+
+/// # Errors
+pub fn take_from_bytes_with_options<D>(input: &[u8], options: Options) -> Result<(D, &[u8])>
+where
+    D: DeserializeOwned,
+{
+    take_from_slice_with_options(input, options)
+}
+
+/// Like [`take_from_bytes`], but strings and bytes may borrow from `input`.
+///
+/// # Errors
+pub fn take_from_slice<'a, T>(input: &'a [u8]) -> Result<(T, &'a [u8])>
+where
+    T: Deserialize<'a>,
+{
+    take_from_slice_with_options(input, Options::default())
+}
+
+/// # Errors
+pub fn take_from_slice_with_options<'a, T>(
+    input: &'a [u8],
+    options: Options,
+) -> Result<(T, &'a [u8])>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::from_slice_with_options(input, options);
+    let value = T::deserialize(&mut deserializer).map_err(|err| err.at(deserializer.position()))?;
+    Ok((value, deserializer.into_inner().remaining()))
+}
+
+impl<'de, R: Read<'de>> de::Deserializer<'de> for &mut Deserializer<R> {
     type Error = Error;
 
+    fn is_human_readable(&self) -> bool {
+        self.options.human_readable()
+    }
+
     fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        Err(self.unsupported("deserialize_any"))
     }
 
     fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let value = self.input.read_u8().map_err(Error::io)?;
-        visitor.visit_bool(value != 0)
+        let value = self.deserialize_tag()?;
+        visitor.visit_bool(value)
     }
 
     fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let value = self.input.read_i8().map_err(Error::io)?;
+        let value = self.read_i8()?;
         visitor.visit_i8(value)
     }
 
@@ -86,7 +383,10 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
-        let value = self.input.read_i16::<NetworkEndian>().map_err(Error::io)?;
+        let value = match self.options.int_encoding() {
+            IntEncoding::Fixed => self.read_i16(),
+            IntEncoding::Varint => self.read_varint(16).map(|v| varint::unzigzag(v) as i16),
+        }?;
         visitor.visit_i16(value)
     }
 
@@ -94,7 +394,10 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
-        let value = self.input.read_i32::<NetworkEndian>().map_err(Error::io)?;
+        let value = match self.options.int_encoding() {
+            IntEncoding::Fixed => self.read_i32(),
+            IntEncoding::Varint => self.read_varint(32).map(|v| varint::unzigzag(v) as i32),
+        }?;
         visitor.visit_i32(value)
     }
 
@@ -102,15 +405,29 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
-        let value = self.input.read_i64::<NetworkEndian>().map_err(Error::io)?;
+        let value = match self.options.int_encoding() {
+            IntEncoding::Fixed => self.read_i64(),
+            IntEncoding::Varint => self.read_varint(64).map(|v| varint::unzigzag(v) as i64),
+        }?;
         visitor.visit_i64(value)
     }
 
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let value = match self.options.int_encoding() {
+            IntEncoding::Fixed => self.read_i128(),
+            IntEncoding::Varint => self.read_varint(128).map(varint::unzigzag),
+        }?;
+        visitor.visit_i128(value)
+    }
+
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let value = self.input.read_u8().map_err(Error::io)?;
+        let value = self.read_u8()?;
         visitor.visit_u8(value)
     }
 
@@ -118,7 +435,10 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
-        let value = self.input.read_u16::<NetworkEndian>().map_err(Error::io)?;
+        let value = match self.options.int_encoding() {
+            IntEncoding::Fixed => self.read_u16(),
+            IntEncoding::Varint => self.read_varint(16).map(|v| v as u16),
+        }?;
         visitor.visit_u16(value)
     }
 
@@ -126,7 +446,10 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
-        let value = self.input.read_u32::<NetworkEndian>().map_err(Error::io)?;
+        let value = match self.options.int_encoding() {
+            IntEncoding::Fixed => self.read_u32(),
+            IntEncoding::Varint => self.read_varint(32).map(|v| v as u32),
+        }?;
         visitor.visit_u32(value)
     }
 
@@ -134,15 +457,29 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
-        let value = self.input.read_u64::<NetworkEndian>().map_err(Error::io)?;
+        let value = match self.options.int_encoding() {
+            IntEncoding::Fixed => self.read_u64(),
+            IntEncoding::Varint => self.read_varint(64).map(|v| v as u64),
+        }?;
         visitor.visit_u64(value)
     }
 
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let value = match self.options.int_encoding() {
+            IntEncoding::Fixed => self.read_u128(),
+            IntEncoding::Varint => self.read_varint(128),
+        }?;
+        visitor.visit_u128(value)
+    }
+
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let value = self.input.read_f32::<NetworkEndian>().map_err(Error::io)?;
+        let value = self.read_f32()?;
         visitor.visit_f32(value)
     }
 
@@ -150,7 +487,7 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
-        let value = self.input.read_f64::<NetworkEndian>().map_err(Error::io)?;
+        let value = self.read_f64()?;
         visitor.visit_f64(value)
     }
 
@@ -158,55 +495,62 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
-        let value = self.input.read_u32::<NetworkEndian>().map_err(Error::io)?;
-        let c = char::from_u32(value).ok_or(Error::InvalidChar)?;
+        let start = self.position();
+        let value = match self.options.int_encoding() {
+            IntEncoding::Fixed => self.read_u32(),
+            IntEncoding::Varint => self.read_varint(32).map(|v| v as u32),
+        }?;
+        let c =
+            char::from_u32(value).ok_or_else(|| Error::from(ErrorKind::InvalidChar).at(start))?;
         visitor.visit_char(c)
     }
 
-    fn deserialize_str<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        let start = self.position();
+        let invalid = |_| Error::from(ErrorKind::InvalidString).at(start);
+        match self.deserialize_length_prefixed()? {
+            Reference::Borrowed(bytes) => {
+                visitor.visit_borrowed_str(str::from_utf8(bytes).map_err(invalid)?)
+            }
+            Reference::Copied(bytes) => visitor.visit_str(str::from_utf8(bytes).map_err(invalid)?),
+        }
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let length = self.input.read_u16::<NetworkEndian>().map_err(Error::io)?;
-        let mut bytes = vec![0; length as usize];
-        self.input.read_exact(&mut bytes).map_err(Error::io)?;
-        let s = String::from_utf8(bytes).map_err(|_| Error::InvalidString)?;
-        visitor.visit_string(s)
+        self.deserialize_str(visitor)
     }
 
-    fn deserialize_bytes<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        match self.deserialize_length_prefixed()? {
+            Reference::Borrowed(bytes) => visitor.visit_borrowed_bytes(bytes),
+            Reference::Copied(bytes) => visitor.visit_bytes(bytes),
+        }
     }
 
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let length = self.input.read_u16::<NetworkEndian>().map_err(Error::io)?;
-        let mut bytes = vec![0; length as usize];
-        self.input.read_exact(&mut bytes).map_err(Error::io)?;
-        visitor.visit_bytes(&bytes)
+        self.deserialize_bytes(visitor)
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let value = self.input.read_u8().map_err(Error::io)?;
-        if value == 0 {
-            visitor.visit_none()
+        if self.deserialize_tag()? {
+            self.nested(|de| visitor.visit_some(de))
         } else {
-            visitor.visit_some(self)
+            visitor.visit_none()
         }
     }
 
@@ -228,167 +572,314 @@ impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_newtype_struct(self)
+        self.nested(|de| visitor.visit_newtype_struct(de))
     }
 
     fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let length = self.input.read_u16::<NetworkEndian>().map_err(Error::io)?;
-        visitor.visit_seq(LengthDefined::new(self, length))
+        let length = self.deserialize_length()?;
+        self.nested(|de| visitor.visit_seq(LengthDefined::new(de, length, None)))
     }
 
-    #[allow(clippy::cast_possible_truncation)]
     fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_seq(LengthDefined::new(self, len as u16))
+        self.nested(|de| visitor.visit_seq(LengthDefined::new(de, len, None)))
     }
 
-    #[allow(clippy::cast_possible_truncation)]
     fn deserialize_tuple_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         len: usize,
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_seq(LengthDefined::new(self, len as u16))
+        self.deserialize_tuple(len, visitor)
+            .map_err(|err| err.in_segment(Segment::Type(name)))
     }
 
     fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let length = self.input.read_u16::<NetworkEndian>().map_err(Error::io)?;
-        visitor.visit_map(LengthDefined::new(self, length))
+        let length = self.deserialize_length()?;
+        self.nested(|de| visitor.visit_map(LengthDefined::new(de, length, None)))
     }
 
     fn deserialize_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_tuple(fields.len(), visitor)
+        self.nested(|de| visitor.visit_seq(LengthDefined::new(de, fields.len(), Some(fields))))
+            .map_err(|err| err.in_segment(Segment::Type(name)))
     }
 
     fn deserialize_enum<V>(
         self,
-        _name: &'static str,
-        _variants: &'static [&'static str],
+        name: &'static str,
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_enum(Enum::new(self))
+        self.nested(|de| visitor.visit_enum(Enum::new(de, variants)))
+            .map_err(|err| err.in_segment(Segment::Type(name)))
     }
 
     fn deserialize_identifier<V>(self, _visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        Err(self.unsupported("deserialize_identifier"))
     }
 
     fn deserialize_ignored_any<V>(self, _visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        Err(self.unsupported("deserialize_ignored_any"))
+    }
+}
+
+/// Resources counted against [`Limits`] while deserializing.
+pub(crate) struct Usage {
+    depth: usize,
+    allocated: u64,
+}
+
+impl Usage {
+    pub(crate) const fn new() -> Self {
+        Usage {
+            depth: 0,
+            allocated: 0,
+        }
+    }
+}
+
+/// State and reads that the limit checks and compound access types below need
+/// from a deserializer, so that
+/// [`BitDeserializer`](crate::bits::BitDeserializer) can share them.
+pub(crate) trait Decode<'de> {
+    fn position(&self) -> u64;
+    fn limits(&self) -> Limits;
+    fn usage(&mut self) -> &mut Usage;
+    fn deserialize_discriminant(&mut self) -> Result<u32>;
+
+    /// Counts a string or byte buffer of `len` bytes against the allocation
+    /// limits.
+    fn check_alloc(&mut self, len: usize) -> Result<()> {
+        let limits = self.limits();
+        let position = self.position();
+        let length = len as u64;
+        match limits.max_alloc() {
+            Some(limit) if length > limit => {
+                return Err(
+                    Error::from(ErrorKind::AllocationLimitExceeded { length, limit }).at(position),
+                );
+            }
+            _ => {}
+        }
+        let usage = self.usage();
+        usage.allocated = usage.allocated.saturating_add(length);
+        match limits.max_total_alloc() {
+            Some(limit) if usage.allocated > limit => {
+                Err(Error::from(ErrorKind::TotalAllocationLimitExceeded { limit }).at(position))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Runs `f` one level deeper, failing if that exceeds the depth limit.
+    fn nested<F, T>(&mut self, f: F) -> Result<T>
+    where
+        Self: Sized,
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        match self.limits().max_depth() {
+            Some(limit) if self.usage().depth >= limit => {
+                return Err(
+                    Error::from(ErrorKind::DepthLimitExceeded { limit }).at(self.position())
+                );
+            }
+            _ => {}
+        }
+        self.usage().depth += 1;
+        let result = f(self);
+        self.usage().depth -= 1;
+        result
+    }
+}
+
+impl<'de, R: Read<'de>> Decode<'de> for Deserializer<R> {
+    fn position(&self) -> u64 {
+        Deserializer::position(self)
+    }
+
+    fn limits(&self) -> Limits {
+        self.options.limits()
+    }
+
+    fn usage(&mut self) -> &mut Usage {
+        &mut self.usage
+    }
+
+    fn deserialize_discriminant(&mut self) -> Result<u32> {
+        Deserializer::deserialize_discriminant(self)
     }
 }
 
-struct LengthDefined<'a, R: Read> {
-    de: &'a mut Deserializer<R>,
-    length: u16,
-    index: u16,
+pub(crate) struct LengthDefined<'a, D> {
+    de: &'a mut D,
+    length: usize,
+    index: usize,
+    fields: Option<&'static [&'static str]>,
 }
 
-impl<'de, 'a, R: Read> LengthDefined<'a, R> {
-    fn new(de: &'a mut Deserializer<R>, length: u16) -> Self {
+impl<'de, 'a, D> LengthDefined<'a, D>
+where
+    D: Decode<'de>,
+    for<'b> &'b mut D: de::Deserializer<'de, Error = Error>,
+{
+    pub(crate) fn new(
+        de: &'a mut D,
+        length: usize,
+        fields: Option<&'static [&'static str]>,
+    ) -> Self {
         LengthDefined {
             de,
             length,
             index: 0,
+            fields,
         }
     }
 
-    fn next_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    fn deserialize_in<T>(&mut self, segment: Segment, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let start = self.de.position();
+        seed.deserialize(&mut *self.de)
+            .map_err(|err| err.at(start).in_segment(segment))
+    }
+
+    fn next_seed<T>(&mut self, segment: Segment, seed: T) -> Result<Option<T::Value>>
     where
         T: DeserializeSeed<'de>,
     {
         if self.index < self.length {
             self.index += 1;
-            seed.deserialize(&mut *self.de).map(Some)
+            self.deserialize_in(segment, seed).map(Some)
         } else {
             Ok(None)
         }
     }
 }
 
-impl<'de, 'a, R: Read> de::SeqAccess<'de> for LengthDefined<'a, R> {
+impl<'de, 'a, D> de::SeqAccess<'de> for LengthDefined<'a, D>
+where
+    D: Decode<'de>,
+    for<'b> &'b mut D: de::Deserializer<'de, Error = Error>,
+{
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
     where
         T: DeserializeSeed<'de>,
     {
-        self.next_seed::<T>(seed)
+        let segment = match self.fields.and_then(|fields| fields.get(self.index)) {
+            Some(field) => Segment::Field(field),
+            None => Segment::Index(self.index),
+        };
+        self.next_seed::<T>(segment, seed)
     }
 }
 
-impl<'de, 'a, R: Read> de::MapAccess<'de> for LengthDefined<'a, R> {
+impl<'de, 'a, D> de::MapAccess<'de> for LengthDefined<'a, D>
+where
+    D: Decode<'de>,
+    for<'b> &'b mut D: de::Deserializer<'de, Error = Error>,
+{
     type Error = Error;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
     where
         K: DeserializeSeed<'de>,
     {
-        self.next_seed::<K>(seed)
+        self.next_seed::<K>(Segment::MapKey(self.index), seed)
     }
 
     fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
     where
         V: DeserializeSeed<'de>,
     {
-        seed.deserialize(&mut *self.de)
+        let segment = Segment::MapValue(self.index.saturating_sub(1));
+        self.deserialize_in(segment, seed)
     }
 }
 
-struct Enum<'a, R: Read> {
-    de: &'a mut Deserializer<R>,
+pub(crate) struct Enum<'a, D> {
+    de: &'a mut D,
+    variants: &'static [&'static str],
+    variant: Option<&'static str>,
 }
 
-impl<'a, R: Read> Enum<'a, R> {
-    fn new(de: &'a mut Deserializer<R>) -> Self {
-        Enum { de }
+impl<'a, D> Enum<'a, D> {
+    pub(crate) fn new(de: &'a mut D, variants: &'static [&'static str]) -> Self {
+        Enum {
+            de,
+            variants,
+            variant: None,
+        }
+    }
+
+    fn in_variant(&self, err: Error) -> Error {
+        match self.variant {
+            Some(variant) => err.in_segment(Segment::Variant(variant)),
+            None => err,
+        }
     }
 }
 
-impl<'de, 'a, R: Read> de::EnumAccess<'de> for Enum<'a, R> {
+impl<'de, 'a, D> de::EnumAccess<'de> for Enum<'a, D>
+where
+    D: Decode<'de>,
+    for<'b> &'b mut D: de::Deserializer<'de, Error = Error>,
+{
     type Error = Error;
     type Variant = Self;
 
-    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    fn variant_seed<V>(mut self, seed: V) -> Result<(V::Value, Self::Variant)>
     where
         V: DeserializeSeed<'de>,
     {
-        let index = self.de.input.read_u8().map_err(Error::io)?;
-        let value = seed.deserialize(index.into_deserializer())?;
+        let start = self.de.position();
+        let index = self.de.deserialize_discriminant()?;
+        let value = seed
+            .deserialize(index.into_deserializer())
+            .map_err(|err: Error| err.at(start))?;
+        self.variant = usize::try_from(index)
+            .ok()
+            .and_then(|index| self.variants.get(index).copied());
         Ok((value, self))
     }
 }
 
-impl<'de, 'a, R: Read> de::VariantAccess<'de> for Enum<'a, R> {
+impl<'de, 'a, D> de::VariantAccess<'de> for Enum<'a, D>
+where
+    D: Decode<'de>,
+    for<'b> &'b mut D: de::Deserializer<'de, Error = Error>,
+{
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
@@ -399,20 +890,28 @@ impl<'de, 'a, R: Read> de::VariantAccess<'de> for Enum<'a, R> {
     where
         T: DeserializeSeed<'de>,
     {
-        seed.deserialize(self.de)
+        seed.deserialize(&mut *self.de)
+            .map_err(|err| self.in_variant(err))
     }
 
     fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        de::Deserializer::deserialize_tuple(self.de, len, visitor)
+        de::Deserializer::deserialize_tuple(&mut *self.de, len, visitor)
+            .map_err(|err| self.in_variant(err))
     }
 
     fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        de::Deserializer::deserialize_struct(self.de, "", fields, visitor)
+        visitor
+            .visit_seq(LengthDefined::new(
+                &mut *self.de,
+                fields.len(),
+                Some(fields),
+            ))
+            .map_err(|err| self.in_variant(err))
     }
 }
@@ -0,0 +1,173 @@
+use core::fmt;
+use core::marker::PhantomData;
+
+use byteorder::ByteOrder;
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser::{SerializeTuple, Serializer};
+use serde::Deserializer;
+
+pub(crate) mod private {
+    pub trait Sealed {}
+}
+
+const MAX_SIZE: usize = 16;
+
+/// Numeric types that can be encoded with a fixed byte order through the
+/// [`le`] and [`be`] modules.
+pub trait Primitive: Sized + private::Sealed {
+    #[doc(hidden)]
+    const SIZE: usize;
+
+    #[doc(hidden)]
+    fn write<B: ByteOrder>(&self, buf: &mut [u8]);
+
+    #[doc(hidden)]
+    fn read<B: ByteOrder>(buf: &[u8]) -> Self;
+}
+
+macro_rules! impl_primitive {
+    ($($ty:ty: $size:expr, $write:ident, $read:ident;)*) => {$(
+        impl private::Sealed for $ty {}
+
+        impl Primitive for $ty {
+            const SIZE: usize = $size;
+
+            fn write<B: ByteOrder>(&self, buf: &mut [u8]) {
+                B::$write(buf, *self);
+            }
+
+            fn read<B: ByteOrder>(buf: &[u8]) -> Self {
+                B::$read(buf)
+            }
+        }
+    )*};
+}
+
+impl_primitive! {
+    i16: 2, write_i16, read_i16;
+    i32: 4, write_i32, read_i32;
+    i64: 8, write_i64, read_i64;
+    u16: 2, write_u16, read_u16;
+    u32: 4, write_u32, read_u32;
+    u64: 8, write_u64, read_u64;
+    i128: 16, write_i128, read_i128;
+    u128: 16, write_u128, read_u128;
+    f32: 4, write_f32, read_f32;
+    f64: 8, write_f64, read_f64;
+}
+
+pub(crate) fn serialize<B, T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    B: ByteOrder,
+    T: Primitive,
+    S: Serializer,
+{
+    let mut buf = [0; MAX_SIZE];
+    let bytes = &mut buf[..T::SIZE];
+    value.write::<B>(bytes);
+    let mut tuple = serializer.serialize_tuple(T::SIZE)?;
+    for byte in bytes.iter() {
+        tuple.serialize_element(byte)?;
+    }
+    tuple.end()
+}
+
+pub(crate) fn deserialize<'de, B, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    B: ByteOrder,
+    T: Primitive,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_tuple(T::SIZE, PrimitiveVisitor::<B, T>(PhantomData))
+}
+
+struct PrimitiveVisitor<B, T>(PhantomData<(B, T)>);
+
+impl<'de, B, T> Visitor<'de> for PrimitiveVisitor<B, T>
+where
+    B: ByteOrder,
+    T: Primitive,
+{
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{} bytes", T::SIZE)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<T, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut buf = [0; MAX_SIZE];
+        for (i, byte) in buf[..T::SIZE].iter_mut().enumerate() {
+            *byte = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+        }
+        Ok(T::read::<B>(&buf[..T::SIZE]))
+    }
+}
+
+/// Little-endian encoding for a single field, regardless of the byte order of
+/// the surrounding [`Serializer`](crate::Serializer).
+///
+/// ```
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Serialize, Deserialize)]
+/// struct Header {
+///     length: u16,
+///     #[serde(with = "serde_net::le")]
+///     counter: u32,
+/// }
+/// ```
+pub mod le {
+    use byteorder::LittleEndian;
+    use serde::{Deserializer, Serializer};
+
+    use super::Primitive;
+
+    /// # Errors
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Primitive,
+        S: Serializer,
+    {
+        super::serialize::<LittleEndian, T, S>(value, serializer)
+    }
+
+    /// # Errors
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Primitive,
+        D: Deserializer<'de>,
+    {
+        super::deserialize::<LittleEndian, T, D>(deserializer)
+    }
+}
+
+/// Big-endian encoding for a single field, regardless of the byte order of
+/// the surrounding [`Serializer`](crate::Serializer).
+pub mod be {
+    use byteorder::BigEndian;
+    use serde::{Deserializer, Serializer};
+
+    use super::Primitive;
+
+    /// # Errors
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Primitive,
+        S: Serializer,
+    {
+        super::serialize::<BigEndian, T, S>(value, serializer)
+    }
+
+    /// # Errors
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Primitive,
+        D: Deserializer<'de>,
+    {
+        super::deserialize::<BigEndian, T, D>(deserializer)
+    }
+}
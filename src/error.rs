@@ -1,27 +1,167 @@
+#[cfg(feature = "alloc")]
+use alloc::string::{String, ToString};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
 use serde::{de, ser};
-use std::error;
-use std::fmt::{self, Display};
+#[cfg(feature = "std")]
 use std::io;
 
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
 
+/// An error from serializing or deserializing, together with where in the
+/// input it happened.
 #[derive(Debug)]
-pub enum Error {
-    Message(String),
+pub struct Error {
+    kind: ErrorKind,
+    offset: Option<u64>,
+    #[cfg(feature = "alloc")]
+    path: Vec<Segment>,
+}
+
+/// One step of the path from the top-level value to the value that failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Segment {
+    /// Name of a struct or enum type. Only displayed at the start of a path.
+    Type(&'static str),
+    Field(&'static str),
+    Index(usize),
+    /// Key of the map entry at this index.
+    MapKey(usize),
+    /// Value of the map entry at this index.
+    MapValue(usize),
+    Variant(&'static str),
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Custom error raised by a `Serialize` or `Deserialize` impl.
+    Message(Message),
+    #[cfg(feature = "std")]
     Io(io::Error),
     LengthNotKnown,
+    LengthOverflow {
+        length: u64,
+        max: u64,
+    },
+    DiscriminantOverflow {
+        index: u32,
+        max: u32,
+    },
+    VarintOverflow,
+    VarintOverlong,
+    QuicVarIntOverflow {
+        value: u64,
+    },
+    /// Returned only by the `TryFrom` conversions of [`U24`](crate::U24),
+    /// [`I24`](crate::I24) and [`U48`](crate::U48). The field adapters in
+    /// [`u24`](crate::u24), [`i24`](crate::i24) and [`u48`](crate::u48) go
+    /// through the generic serde error, so they fail with
+    /// [`ErrorKind::Message`] holding the same text.
+    IntegerOverflow {
+        value: i128,
+        ty: &'static str,
+    },
+    InvalidTag(u8),
     InvalidString,
     InvalidChar,
     TrailingBytes,
+    InvalidPadding,
+    UnalignedEnd,
+    InvalidBitWidth {
+        bits: u32,
+        max: u32,
+    },
+    IntegerWidthOverflow {
+        ty: &'static str,
+        bits: u32,
+    },
+    BufferTooSmall,
+    SizeLimitExceeded {
+        limit: u64,
+    },
+    AllocationLimitExceeded {
+        length: u64,
+        limit: u64,
+    },
+    TotalAllocationLimitExceeded {
+        limit: u64,
+    },
+    DepthLimitExceeded {
+        limit: usize,
+    },
+    Unsupported(&'static str),
     EofWhileDeserializing,
 }
 
 impl Error {
+    #[cfg(feature = "std")]
     pub(crate) fn io(err: io::Error) -> Self {
         if err.kind() == io::ErrorKind::UnexpectedEof {
-            Error::EofWhileDeserializing
+            ErrorKind::EofWhileDeserializing.into()
         } else {
-            Error::Io(err)
+            ErrorKind::Io(err).into()
+        }
+    }
+
+    /// Records the byte offset of the error unless one is already known.
+    pub(crate) fn at(mut self, offset: u64) -> Self {
+        self.offset.get_or_insert(offset);
+        self
+    }
+
+    /// Prepends a segment to the path as the error propagates outwards.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn in_segment(mut self, segment: Segment) -> Self {
+        self.path.insert(0, segment);
+        self
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    pub(crate) fn in_segment(self, _segment: Segment) -> Self {
+        self
+    }
+
+    #[must_use]
+    pub const fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    #[must_use]
+    pub fn into_kind(self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Byte offset into the input at which deserialization failed.
+    ///
+    /// Always `None` for errors from the [`Serializer`](crate::Serializer).
+    #[must_use]
+    pub const fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+
+    /// Path from the top-level value to the value that failed, outermost
+    /// first.
+    ///
+    /// Always empty without the `alloc` feature.
+    #[must_use]
+    pub fn path(&self) -> &[Segment] {
+        #[cfg(feature = "alloc")]
+        let path = &self.path;
+        #[cfg(not(feature = "alloc"))]
+        let path = &[];
+        path
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Error {
+            kind,
+            offset: None,
+            #[cfg(feature = "alloc")]
+            path: Vec::new(),
         }
     }
 }
@@ -31,7 +171,7 @@ impl ser::Error for Error {
     where
         T: Display,
     {
-        Error::Message(msg.to_string())
+        ErrorKind::Message(Message::new(msg)).into()
     }
 }
 
@@ -40,22 +180,161 @@ impl de::Error for Error {
     where
         T: Display,
     {
-        Error::Message(msg.to_string())
+        ErrorKind::Message(Message::new(msg)).into()
+    }
+}
+
+/// Text of a custom error.
+///
+/// Without the `alloc` feature the text is kept in a fixed buffer and cut
+/// off after [`Message::CAPACITY`] bytes.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Message {
+    #[cfg(feature = "alloc")]
+    text: String,
+    #[cfg(not(feature = "alloc"))]
+    buf: [u8; Message::CAPACITY],
+    #[cfg(not(feature = "alloc"))]
+    len: usize,
+}
+
+impl Message {
+    /// Number of bytes kept without the `alloc` feature.
+    pub const CAPACITY: usize = 64;
+
+    #[cfg(feature = "alloc")]
+    fn new<T: Display>(msg: T) -> Self {
+        Message {
+            text: msg.to_string(),
+        }
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn new<T: Display>(msg: T) -> Self {
+        use core::fmt::Write;
+
+        let mut message = Message {
+            buf: [0; Message::CAPACITY],
+            len: 0,
+        };
+        let _ = write!(message, "{}", msg);
+        message
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        #[cfg(feature = "alloc")]
+        let text = &self.text;
+        #[cfg(not(feature = "alloc"))]
+        let text = core::str::from_utf8(&self.buf[..self.len]).unwrap_or_default();
+        text
+    }
+}
+
+/// Appends as much of each string as fits, cutting at a char boundary.
+#[cfg(not(feature = "alloc"))]
+impl fmt::Write for Message {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut end = s.len().min(Message::CAPACITY - self.len);
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.buf[self.len..self.len + end].copy_from_slice(&s.as_bytes()[..end]);
+        self.len += end;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
     }
 }
 
 impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path();
+        if !path.is_empty() {
+            for (i, segment) in path.iter().enumerate() {
+                match *segment {
+                    Segment::Type(name) if i == 0 => f.write_str(name)?,
+                    Segment::Type(_) => {}
+                    Segment::Field(name) if i == 0 => f.write_str(name)?,
+                    Segment::Field(name) => write!(f, ".{}", name)?,
+                    Segment::Index(index) => write!(f, "[{}]", index)?,
+                    Segment::MapKey(index) => write!(f, "[key {}]", index)?,
+                    Segment::MapValue(index) => write!(f, "[value {}]", index)?,
+                    Segment::Variant(name) => write!(f, "::{}", name)?,
+                }
+            }
+            f.write_str(": ")?;
+        }
+        Display::fmt(&self.kind, f)?;
+        if let Some(offset) = self.offset {
+            write!(f, " at offset {}", offset)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Error::Message(msg) => f.write_str(msg),
-            Error::Io(ref err) => Display::fmt(err, f),
-            Error::LengthNotKnown => f.write_str("length not known"),
-            Error::InvalidString => f.write_str("invalid string"),
-            Error::InvalidChar => f.write_str("invalid char"),
-            Error::TrailingBytes => f.write_str("trailing bytes"),
-            Error::EofWhileDeserializing => f.write_str("EOF while deserializing"),
+            ErrorKind::Message(msg) => Display::fmt(msg, f),
+            #[cfg(feature = "std")]
+            ErrorKind::Io(ref err) => Display::fmt(err, f),
+            ErrorKind::LengthNotKnown => f.write_str("length not known"),
+            ErrorKind::LengthOverflow { length, max } => {
+                write!(f, "length {} exceeds maximum of {}", length, max)
+            }
+            ErrorKind::DiscriminantOverflow { index, max } => {
+                write!(f, "variant index {} exceeds maximum of {}", index, max)
+            }
+            ErrorKind::VarintOverflow => f.write_str("varint overflows integer"),
+            ErrorKind::VarintOverlong => f.write_str("varint is not minimally encoded"),
+            ErrorKind::QuicVarIntOverflow { value } => {
+                write!(f, "{} exceeds the QUIC varint maximum of 2^62 - 1", value)
+            }
+            ErrorKind::IntegerOverflow { value, ty } => {
+                write!(f, "{} is out of range for {}", value, ty)
+            }
+            ErrorKind::InvalidTag(tag) => write!(f, "invalid tag {}, expected 0 or 1", tag),
+            ErrorKind::InvalidString => f.write_str("invalid string"),
+            ErrorKind::InvalidChar => f.write_str("invalid char"),
+            ErrorKind::TrailingBytes => f.write_str("trailing bytes"),
+            ErrorKind::InvalidPadding => f.write_str("invalid padding bits"),
+            ErrorKind::InvalidBitWidth { bits, max } => {
+                write!(f, "bit width {} is not between 1 and {}", bits, max)
+            }
+            ErrorKind::IntegerWidthOverflow { ty, bits } => {
+                write!(f, "{} value does not fit in {} bits", ty, bits)
+            }
+            ErrorKind::UnalignedEnd => f.write_str("bit stream does not end on a byte boundary"),
+            ErrorKind::BufferTooSmall => f.write_str("output buffer too small"),
+            ErrorKind::SizeLimitExceeded { limit } => {
+                write!(f, "input exceeds size limit of {} bytes", limit)
+            }
+            ErrorKind::AllocationLimitExceeded { length, limit } => write!(
+                f,
+                "length {} exceeds allocation limit of {} bytes",
+                length, limit
+            ),
+            ErrorKind::TotalAllocationLimitExceeded { limit } => {
+                write!(f, "total allocation exceeds limit of {} bytes", limit)
+            }
+            ErrorKind::DepthLimitExceeded { limit } => {
+                write!(f, "nesting exceeds depth limit of {}", limit)
+            }
+            ErrorKind::Unsupported(method) => write!(f, "{} is not supported", method),
+            ErrorKind::EofWhileDeserializing => f.write_str("EOF while deserializing"),
         }
     }
 }
 
-impl error::Error for Error {}
+impl de::StdError for Error {}
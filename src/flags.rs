@@ -0,0 +1,104 @@
+//! `with` modules for [`bitflags`] types, which are encoded as their
+//! underlying integer.
+//!
+//! The modules differ in how bits that match no defined flag are handled when
+//! deserializing: [`reject`] fails, [`truncate`] drops them and [`retain`]
+//! keeps them. All three serialize every bit of the value.
+//!
+//! ```
+//! use bitflags::bitflags;
+//! use serde::{Deserialize, Serialize};
+//! use serde_net::{from_slice, to_vec};
+//!
+//! bitflags! {
+//!     #[derive(Debug, PartialEq)]
+//!     pub struct TcpFlags: u8 {
+//!         const FIN = 0x01;
+//!         const SYN = 0x02;
+//!         const ACK = 0x10;
+//!     }
+//! }
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! struct Segment {
+//!     #[serde(with = "serde_net::flags::truncate")]
+//!     flags: TcpFlags,
+//! }
+//!
+//! let segment = Segment { flags: TcpFlags::SYN | TcpFlags::ACK };
+//! assert_eq!(to_vec(&segment).unwrap(), [0x12]);
+//! assert_eq!(from_slice::<Segment>(&[0x52]).unwrap(), segment);
+//! ```
+
+use bitflags::Flags;
+use serde::{Serialize, Serializer};
+
+/// # Errors
+pub fn serialize<T, S>(flags: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Flags,
+    T::Bits: Serialize,
+    S: Serializer,
+{
+    flags.bits().serialize(serializer)
+}
+
+/// Fails on bits that match no defined flag.
+pub mod reject {
+    use core::fmt::LowerHex;
+
+    use bitflags::Flags;
+    use serde::{de, Deserialize, Deserializer};
+
+    pub use super::serialize;
+
+    /// # Errors
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Flags,
+        T::Bits: Deserialize<'de> + LowerHex,
+        D: Deserializer<'de>,
+    {
+        let bits = T::Bits::deserialize(deserializer)?;
+        T::from_bits(bits).ok_or_else(|| {
+            let unknown = bits & !T::all().bits();
+            de::Error::custom(format_args!("unknown flag bits {:#x}", unknown))
+        })
+    }
+}
+
+/// Drops bits that match no defined flag.
+pub mod truncate {
+    use bitflags::Flags;
+    use serde::{Deserialize, Deserializer};
+
+    pub use super::serialize;
+
+    /// # Errors
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Flags,
+        T::Bits: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        T::Bits::deserialize(deserializer).map(T::from_bits_truncate)
+    }
+}
+
+/// Keeps bits that match no defined flag, so they survive a roundtrip.
+pub mod retain {
+    use bitflags::Flags;
+    use serde::{Deserialize, Deserializer};
+
+    pub use super::serialize;
+
+    /// # Errors
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Flags,
+        T::Bits: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        T::Bits::deserialize(deserializer).map(T::from_bits_retain)
+    }
+}
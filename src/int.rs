@@ -0,0 +1,144 @@
+use core::fmt::{self, Display};
+
+use byteorder::{BigEndian, ByteOrder};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::endian::{self, private, Primitive};
+use crate::error::{Error, ErrorKind};
+
+macro_rules! odd_int {
+    ($(
+        $(#[$attr:meta])*
+        $name:ident($repr:ty): $module:ident, $size:expr, $min:expr, $max:expr, $write:ident, $read:ident;
+    )*) => {$(
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name($repr);
+
+        impl $name {
+            pub const MIN: $name = $name($min);
+            pub const MAX: $name = $name($max);
+
+            /// Returns `None` if `value` is outside [`MIN`](Self::MIN) to
+            /// [`MAX`](Self::MAX).
+            #[must_use]
+            #[allow(unused_comparisons)]
+            pub const fn new(value: $repr) -> Option<Self> {
+                if value >= $min && value <= $max {
+                    Some($name(value))
+                } else {
+                    None
+                }
+            }
+
+            #[must_use]
+            pub const fn into_inner(self) -> $repr {
+                self.0
+            }
+        }
+
+        impl TryFrom<$repr> for $name {
+            type Error = Error;
+
+            fn try_from(value: $repr) -> Result<Self, Error> {
+                $name::new(value).ok_or_else(|| {
+                    ErrorKind::IntegerOverflow {
+                        value: value.into(),
+                        ty: stringify!($module),
+                    }
+                    .into()
+                })
+            }
+        }
+
+        impl From<$name> for $repr {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                Display::fmt(&self.0, f)
+            }
+        }
+
+        impl private::Sealed for $name {}
+
+        impl Primitive for $name {
+            const SIZE: usize = $size;
+
+            fn write<B: ByteOrder>(&self, buf: &mut [u8]) {
+                B::$write(buf, self.0);
+            }
+
+            fn read<B: ByteOrder>(buf: &[u8]) -> Self {
+                $name(B::$read(buf))
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                endian::serialize::<BigEndian, Self, S>(self, serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                endian::deserialize::<BigEndian, Self, D>(deserializer)
+            }
+        }
+
+        #[doc = concat!("Encodes a `", stringify!($repr), "` field as a big-endian [`", stringify!($name), "`].")]
+        ///
+        /// Serializing a value out of range fails with a custom error, which
+        /// is [`ErrorKind::Message`](crate::ErrorKind::Message) with this
+        /// crate's serializers, not
+        /// [`ErrorKind::IntegerOverflow`](crate::ErrorKind::IntegerOverflow).
+        pub mod $module {
+            use serde::{ser, Deserialize, Deserializer, Serialize, Serializer};
+
+            use super::$name;
+
+            /// # Errors
+            pub fn serialize<S>(value: &$repr, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                $name::try_from(*value)
+                    .map_err(ser::Error::custom)?
+                    .serialize(serializer)
+            }
+
+            /// # Errors
+            pub fn deserialize<'de, D>(deserializer: D) -> Result<$repr, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                $name::deserialize(deserializer).map(<$repr>::from)
+            }
+        }
+    )*};
+}
+
+odd_int! {
+    /// Unsigned 24-bit integer, encoded as 3 big-endian bytes.
+    ///
+    /// Use [`le`](crate::le) on the field for little-endian.
+    U24(u32): u24, 3, 0, 0xff_ffff, write_u24, read_u24;
+    /// Signed 24-bit integer, encoded as 3 big-endian bytes in two's
+    /// complement.
+    ///
+    /// Use [`le`](crate::le) on the field for little-endian.
+    I24(i32): i24, 3, -0x80_0000, 0x7f_ffff, write_i24, read_i24;
+    /// Unsigned 48-bit integer, encoded as 6 big-endian bytes.
+    ///
+    /// Use [`le`](crate::le) on the field for little-endian.
+    U48(u64): u48, 6, 0, 0xffff_ffff_ffff, write_u48, read_u48;
+}
@@ -1,7 +1,44 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+mod bitfield;
+pub mod bits;
 mod de;
+mod endian;
 mod error;
+#[cfg(feature = "bitflags")]
+pub mod flags;
+mod int;
+mod options;
+mod quic;
+pub mod read;
 mod ser;
+mod varint;
+pub mod write;
+
+pub use de::{
+    from_bytes, from_bytes_with_options, from_slice, from_slice_with_options, take_from_bytes,
+    take_from_bytes_with_options, take_from_slice, take_from_slice_with_options, Deserializer,
+    StreamDeserializer,
+};
+#[cfg(feature = "std")]
+pub use de::{from_reader, from_reader_with_options};
+pub use endian::{be, le, Primitive};
+pub use error::{Error, ErrorKind, Message, Result, Segment};
+pub use int::{i24, u24, u48, I24, U24, U48};
+pub use options::{Discriminant, Endian, IntEncoding, LengthPrefix, Limits, Options};
+pub use quic::QuicVarInt;
+pub use ser::{
+    serialized_size, serialized_size_with_options, to_slice, to_slice_with_options, to_writer,
+    to_writer_with_options, Serializer,
+};
+#[cfg(feature = "alloc")]
+pub use ser::{to_vec, to_vec_with_options};
 
-pub use de::{from_bytes, from_reader, Deserializer};
-pub use error::{Error, Result};
-pub use ser::{to_vec, to_writer, Serializer};
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::bitfield::{pack, unpack};
+    pub use serde;
+}
@@ -0,0 +1,280 @@
+use crate::quic::QuicVarInt;
+
+/// Width of the length prefix written before strings, bytes, sequences and maps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LengthPrefix {
+    U8,
+    U16,
+    U32,
+    U64,
+    /// Unsigned LEB128.
+    Varint,
+    /// [`QuicVarInt`](crate::QuicVarInt), in network byte order.
+    QuicVarInt,
+}
+
+impl LengthPrefix {
+    /// Largest length that fits in this prefix.
+    #[must_use]
+    pub const fn max(self) -> u64 {
+        match self {
+            LengthPrefix::U8 => u8::MAX as u64,
+            LengthPrefix::U16 => u16::MAX as u64,
+            LengthPrefix::U32 => u32::MAX as u64,
+            LengthPrefix::U64 | LengthPrefix::Varint => u64::MAX,
+            LengthPrefix::QuicVarInt => QuicVarInt::MAX.into_inner(),
+        }
+    }
+}
+
+/// Encoding of the variant index written before every enum variant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Discriminant {
+    U8,
+    U16,
+    U32,
+    /// Unsigned LEB128.
+    Varint,
+}
+
+impl Discriminant {
+    /// Largest variant index that fits in this encoding.
+    #[must_use]
+    pub const fn max(self) -> u32 {
+        match self {
+            Discriminant::U8 => u8::MAX as u32,
+            Discriminant::U16 => u16::MAX as u32,
+            Discriminant::U32 | Discriminant::Varint => u32::MAX,
+        }
+    }
+}
+
+/// Byte order of multi-byte integers, floats, chars, length prefixes and
+/// discriminants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Endian {
+    Big,
+    Little,
+    Native,
+}
+
+/// Encoding of integers wider than a byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IntEncoding {
+    /// Fixed width, in the configured [`Endian`] byte order.
+    Fixed,
+    /// Unsigned LEB128, with ZigZag for signed integers. Length prefixes and
+    /// discriminants are set separately with [`LengthPrefix::Varint`] and
+    /// [`Discriminant::Varint`].
+    Varint,
+}
+
+/// Bounds on the resources a [`Deserializer`](crate::Deserializer) may use,
+/// for decoding untrusted input.
+///
+/// Nesting depth is limited to [`Limits::DEFAULT_MAX_DEPTH`] by default, so
+/// that deeply nested input cannot overflow the stack; every other limit is
+/// unset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Limits {
+    max_size: Option<u64>,
+    max_alloc: Option<u64>,
+    max_total_alloc: Option<u64>,
+    max_depth: Option<usize>,
+}
+
+impl Limits {
+    /// Default for [`Limits::with_max_depth`], as in `serde_json`.
+    pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+    #[must_use]
+    pub const fn new() -> Self {
+        Limits {
+            max_size: None,
+            max_alloc: None,
+            max_total_alloc: None,
+            max_depth: Some(Limits::DEFAULT_MAX_DEPTH),
+        }
+    }
+
+    /// Maximum number of bytes read from the input.
+    #[must_use]
+    pub const fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Maximum length of a single string or byte buffer.
+    #[must_use]
+    pub const fn with_max_alloc(mut self, max_alloc: u64) -> Self {
+        self.max_alloc = Some(max_alloc);
+        self
+    }
+
+    /// Maximum combined length of all strings and byte buffers.
+    #[must_use]
+    pub const fn with_max_total_alloc(mut self, max_total_alloc: u64) -> Self {
+        self.max_total_alloc = Some(max_total_alloc);
+        self
+    }
+
+    /// Maximum nesting of sequences, maps, structs, enums, options and
+    /// newtypes, [`Limits::DEFAULT_MAX_DEPTH`] unless set. Recursive types
+    /// need stack space for each level, so raise it with care.
+    #[must_use]
+    pub const fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Removes the nesting limit, for trusted input only.
+    #[must_use]
+    pub const fn without_max_depth(mut self) -> Self {
+        self.max_depth = None;
+        self
+    }
+
+    #[must_use]
+    pub const fn max_size(&self) -> Option<u64> {
+        self.max_size
+    }
+
+    #[must_use]
+    pub const fn max_alloc(&self) -> Option<u64> {
+        self.max_alloc
+    }
+
+    #[must_use]
+    pub const fn max_total_alloc(&self) -> Option<u64> {
+        self.max_total_alloc
+    }
+
+    #[must_use]
+    pub const fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits::new()
+    }
+}
+
+/// Encoding options shared by [`Serializer`](crate::Serializer) and
+/// [`Deserializer`](crate::Deserializer).
+///
+/// The defaults match the encoding used by [`to_writer`](crate::to_writer)
+/// and [`from_bytes`](crate::from_bytes).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Options {
+    length_prefix: LengthPrefix,
+    discriminant: Discriminant,
+    endian: Endian,
+    strict: bool,
+    limits: Limits,
+    human_readable: bool,
+    int_encoding: IntEncoding,
+}
+
+impl Options {
+    #[must_use]
+    pub const fn new() -> Self {
+        Options {
+            length_prefix: LengthPrefix::U16,
+            discriminant: Discriminant::U8,
+            endian: Endian::Big,
+            strict: false,
+            limits: Limits::new(),
+            human_readable: false,
+            int_encoding: IntEncoding::Fixed,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_length_prefix(mut self, length_prefix: LengthPrefix) -> Self {
+        self.length_prefix = length_prefix;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_discriminant(mut self, discriminant: Discriminant) -> Self {
+        self.discriminant = discriminant;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_endian(mut self, endian: Endian) -> Self {
+        self.endian = endian;
+        self
+    }
+
+    /// Rejects bool and option tags other than 0 and 1 with
+    /// [`ErrorKind::InvalidTag`](crate::ErrorKind::InvalidTag).
+    #[must_use]
+    pub const fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Reports the format as human readable, so types such as `IpAddr`
+    /// encode as text rather than in binary form.
+    #[must_use]
+    pub const fn with_human_readable(mut self, human_readable: bool) -> Self {
+        self.human_readable = human_readable;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_int_encoding(mut self, int_encoding: IntEncoding) -> Self {
+        self.int_encoding = int_encoding;
+        self
+    }
+
+    #[must_use]
+    pub const fn length_prefix(&self) -> LengthPrefix {
+        self.length_prefix
+    }
+
+    #[must_use]
+    pub const fn discriminant(&self) -> Discriminant {
+        self.discriminant
+    }
+
+    #[must_use]
+    pub const fn endian(&self) -> Endian {
+        self.endian
+    }
+
+    #[must_use]
+    pub const fn strict(&self) -> bool {
+        self.strict
+    }
+
+    #[must_use]
+    pub const fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    #[must_use]
+    pub const fn human_readable(&self) -> bool {
+        self.human_readable
+    }
+
+    #[must_use]
+    pub const fn int_encoding(&self) -> IntEncoding {
+        self.int_encoding
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options::new()
+    }
+}
@@ -0,0 +1,152 @@
+use core::fmt::{self, Display};
+
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser::SerializeTuple;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::{Error, ErrorKind};
+
+/// Variable-length integer from RFC 9000, section 16.
+///
+/// The top two bits of the first byte give the length of the encoding: 1, 2,
+/// 4 or 8 bytes, in network byte order whatever the configured
+/// [`Endian`](crate::Endian). Values are written in the shortest form;
+/// longer forms are accepted when reading.
+///
+/// ```
+/// use serde_net::{to_vec, QuicVarInt};
+///
+/// let value = QuicVarInt::try_from(15_293u64).unwrap();
+/// assert_eq!(to_vec(&value).unwrap(), [0x7b, 0xbd]);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct QuicVarInt(u64);
+
+impl QuicVarInt {
+    pub const MAX: QuicVarInt = QuicVarInt((1 << 62) - 1);
+
+    /// Returns `None` if `value` is greater than [`QuicVarInt::MAX`].
+    #[must_use]
+    pub const fn new(value: u64) -> Option<Self> {
+        if value <= QuicVarInt::MAX.0 {
+            Some(QuicVarInt(value))
+        } else {
+            None
+        }
+    }
+
+    #[must_use]
+    pub const fn into_inner(self) -> u64 {
+        self.0
+    }
+}
+
+impl TryFrom<u64> for QuicVarInt {
+    type Error = Error;
+
+    fn try_from(value: u64) -> Result<Self, Error> {
+        QuicVarInt::new(value).ok_or_else(|| ErrorKind::QuicVarIntOverflow { value }.into())
+    }
+}
+
+macro_rules! impl_from {
+    ($($ty:ty),*) => {$(
+        impl From<$ty> for QuicVarInt {
+            fn from(value: $ty) -> Self {
+                QuicVarInt(value.into())
+            }
+        }
+    )*};
+}
+
+impl_from!(u8, u16, u32);
+
+impl From<QuicVarInt> for u64 {
+    fn from(value: QuicVarInt) -> Self {
+        value.0
+    }
+}
+
+impl Display for QuicVarInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+/// Writes `value`, which must not exceed [`QuicVarInt::MAX`], in its shortest
+/// form.
+pub(crate) fn encode(value: u64, buf: &mut [u8; 8]) -> &[u8] {
+    let (prefix, len) = match value {
+        0..=0x3f => (0b00, 1),
+        0x40..=0x3fff => (0b01, 2),
+        0x4000..=0x3fff_ffff => (0b10, 4),
+        _ => (0b11, 8),
+    };
+    *buf = value.to_be_bytes();
+    let bytes = &mut buf[8 - len..];
+    bytes[0] |= prefix << 6;
+    bytes
+}
+
+/// Reads the rest of the encoding that starts with `first`.
+pub(crate) fn decode<F, E>(first: u8, mut next_byte: F) -> Result<u64, E>
+where
+    F: FnMut() -> Result<u8, E>,
+{
+    let len = 1 << (first >> 6);
+    let mut value = u64::from(first & 0x3f);
+    for _ in 1..len {
+        value = (value << 8) | u64::from(next_byte()?);
+    }
+    Ok(value)
+}
+
+impl Serialize for QuicVarInt {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut buf = [0; 8];
+        let bytes = encode(self.0, &mut buf);
+        let mut tuple = serializer.serialize_tuple(bytes.len())?;
+        for byte in bytes {
+            tuple.serialize_element(byte)?;
+        }
+        tuple.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for QuicVarInt {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_tuple(8, QuicVarIntVisitor)
+    }
+}
+
+struct QuicVarIntVisitor;
+
+impl<'de> Visitor<'de> for QuicVarIntVisitor {
+    type Value = QuicVarInt;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a QUIC variable-length integer")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<QuicVarInt, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut index = 0;
+        let mut next_byte = || {
+            let byte = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(index, &self));
+            index += 1;
+            byte
+        };
+        let first = next_byte()?;
+        decode(first, next_byte).map(QuicVarInt)
+    }
+}
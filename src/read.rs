@@ -0,0 +1,211 @@
+//! Input sources for [`Deserializer`](crate::Deserializer).
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::ops::Deref;
+#[cfg(feature = "std")]
+use std::io;
+
+use crate::error::{Error, ErrorKind, Result};
+
+/// Buffer that readers which cannot lend out their input copy bytes into.
+#[cfg(feature = "alloc")]
+pub(crate) type Scratch = Vec<u8>;
+#[cfg(not(feature = "alloc"))]
+pub(crate) type Scratch = ();
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// A source of bytes for the [`Deserializer`](crate::Deserializer).
+///
+/// This trait is sealed; it is implemented by `IoRead` and [`SliceRead`].
+pub trait Read<'de>: private::Sealed {
+    #[doc(hidden)]
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()>;
+
+    #[doc(hidden)]
+    fn read_bytes<'s>(
+        &'s mut self,
+        len: usize,
+        scratch: &'s mut Scratch,
+    ) -> Result<Reference<'de, 's, [u8]>>;
+
+    #[doc(hidden)]
+    fn is_eof(&mut self) -> Result<bool>;
+
+    #[doc(hidden)]
+    fn end(&mut self) -> Result<()> {
+        if self.is_eof()? {
+            Ok(())
+        } else {
+            Err(Error::from(ErrorKind::TrailingBytes).at(self.position()))
+        }
+    }
+
+    #[doc(hidden)]
+    fn position(&self) -> u64;
+}
+
+/// Bytes either borrowed from the input or copied into a scratch buffer.
+pub enum Reference<'b, 'c, T: ?Sized> {
+    Borrowed(&'b T),
+    Copied(&'c T),
+}
+
+impl<'b, 'c, T: ?Sized> Deref for Reference<'b, 'c, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match *self {
+            Reference::Borrowed(b) => b,
+            Reference::Copied(c) => c,
+        }
+    }
+}
+
+/// Reads from any [`std::io::Read`]. Strings and bytes are copied.
+#[cfg(feature = "std")]
+pub struct IoRead<R> {
+    reader: R,
+    position: u64,
+    /// Byte read ahead by `is_eof` but not yet consumed.
+    peeked: Option<u8>,
+}
+
+#[cfg(feature = "std")]
+impl<R: io::Read> IoRead<R> {
+    pub const fn new(reader: R) -> Self {
+        IoRead {
+            reader,
+            position: 0,
+            peeked: None,
+        }
+    }
+
+    /// Returns the underlying reader. A byte read ahead to look for the end
+    /// of the input is lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: io::Read> private::Sealed for IoRead<R> {}
+
+#[cfg(feature = "std")]
+impl<'de, R: io::Read> Read<'de> for IoRead<R> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        let rest = match (self.peeked, buf.split_first_mut()) {
+            (Some(byte), Some((first, rest))) => {
+                *first = byte;
+                self.peeked = None;
+                rest
+            }
+            _ => buf,
+        };
+        self.reader
+            .read_exact(rest)
+            .map_err(|err| Error::io(err).at(self.position))?;
+        self.position += buf.len() as u64;
+        Ok(())
+    }
+
+    fn read_bytes<'s>(
+        &'s mut self,
+        len: usize,
+        scratch: &'s mut Scratch,
+    ) -> Result<Reference<'de, 's, [u8]>> {
+        // Grow the buffer as bytes arrive rather than trusting the length
+        // prefix with an up-front allocation.
+        scratch.clear();
+        let start = self.position;
+        if len > 0 {
+            scratch.extend(self.peeked.take());
+        }
+        let mut reader = io::Read::take(&mut self.reader, (len - scratch.len()) as u64);
+        let result = io::Read::read_to_end(&mut reader, scratch);
+        self.position += scratch.len() as u64;
+        result.map_err(|err| Error::io(err).at(start))?;
+        if scratch.len() == len {
+            Ok(Reference::Copied(scratch))
+        } else {
+            Err(Error::from(ErrorKind::EofWhileDeserializing).at(start))
+        }
+    }
+
+    fn is_eof(&mut self) -> Result<bool> {
+        if self.peeked.is_some() {
+            return Ok(false);
+        }
+        let mut buf = [0; 1];
+        loop {
+            match self.reader.read(&mut buf) {
+                Ok(0) => return Ok(true),
+                Ok(_) => {
+                    self.peeked = Some(buf[0]);
+                    return Ok(false);
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(Error::io(err).at(self.position)),
+            }
+        }
+    }
+
+    fn position(&self) -> u64 {
+        self.position
+    }
+}
+
+/// Reads from a byte slice. Strings and bytes are borrowed from the slice.
+pub struct SliceRead<'a> {
+    slice: &'a [u8],
+    index: usize,
+}
+
+impl<'a> SliceRead<'a> {
+    pub const fn new(slice: &'a [u8]) -> Self {
+        SliceRead { slice, index: 0 }
+    }
+
+    /// The part of the slice that has not been read yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.slice[self.index..]
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let rest = self.remaining();
+        if len > rest.len() {
+            return Err(Error::from(ErrorKind::EofWhileDeserializing).at(self.position()));
+        }
+        self.index += len;
+        Ok(&rest[..len])
+    }
+}
+
+impl<'a> private::Sealed for SliceRead<'a> {}
+
+impl<'a> Read<'a> for SliceRead<'a> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        let bytes = self.take(buf.len())?;
+        buf.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    fn read_bytes<'s>(
+        &'s mut self,
+        len: usize,
+        _scratch: &'s mut Scratch,
+    ) -> Result<Reference<'a, 's, [u8]>> {
+        self.take(len).map(Reference::Borrowed)
+    }
+
+    fn is_eof(&mut self) -> Result<bool> {
+        Ok(self.index == self.slice.len())
+    }
+
+    fn position(&self) -> u64 {
+        self.index as u64
+    }
+}
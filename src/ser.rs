@@ -1,12 +1,152 @@
-use std::io::Write;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(not(feature = "alloc"))]
+use core::fmt;
 
-use byteorder::{NetworkEndian, WriteBytesExt};
+use byteorder::{BigEndian, ByteOrder, LittleEndian, NativeEndian};
 use serde::{ser, Serialize};
 
-use crate::error::{Error, Result};
+use crate::error::{Error, ErrorKind, Result, Segment};
+use crate::options::{Discriminant, Endian, IntEncoding, LengthPrefix, Options};
+use crate::quic::{self, QuicVarInt};
+use crate::varint;
+use crate::write::{SizeCounter, SliceWrite, Write};
 
 pub struct Serializer<W: Write> {
     output: W,
+    options: Options,
+    written: u64,
+}
+
+macro_rules! write_endian {
+    ($($method:ident: $ty:ty, $size:expr;)*) => {$(
+        fn $method(&mut self, v: $ty) -> Result<()> {
+            let mut buf = [0; $size];
+            match self.options.endian() {
+                Endian::Big => BigEndian::$method(&mut buf, v),
+                Endian::Little => LittleEndian::$method(&mut buf, v),
+                Endian::Native => NativeEndian::$method(&mut buf, v),
+            }
+            self.write_bytes(&buf)
+        }
+    )*};
+}
+
+impl<W: Write> Serializer<W> {
+    pub const fn new(writer: W) -> Self {
+        Serializer::with_options(writer, Options::new())
+    }
+
+    pub const fn with_options(writer: W, options: Options) -> Self {
+        Serializer {
+            output: writer,
+            options,
+            written: 0,
+        }
+    }
+
+    /// Number of bytes written to the output so far.
+    pub const fn bytes_written(&self) -> u64 {
+        self.written
+    }
+
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+
+    write_endian! {
+        write_i16: i16, 2;
+        write_i32: i32, 4;
+        write_i64: i64, 8;
+        write_u16: u16, 2;
+        write_u32: u32, 4;
+        write_u64: u64, 8;
+        write_i128: i128, 16;
+        write_u128: u128, 16;
+        write_f32: f32, 4;
+        write_f64: f64, 8;
+    }
+
+    fn write_i8(&mut self, v: i8) -> Result<()> {
+        self.write_u8(v as u8)
+    }
+
+    fn write_u8(&mut self, v: u8) -> Result<()> {
+        self.write_bytes(&[v])
+    }
+
+    fn write_bytes(&mut self, v: &[u8]) -> Result<()> {
+        self.output.write_all(v)?;
+        self.written += v.len() as u64;
+        Ok(())
+    }
+
+    fn write_varint(&mut self, v: u128) -> Result<()> {
+        let mut buf = [0; varint::MAX_LEN];
+        self.write_bytes(varint::encode(v, &mut buf))
+    }
+
+    fn serialize_length(&mut self, len: usize) -> Result<()> {
+        let length_prefix = self.options.length_prefix();
+        let overflow = || {
+            Error::from(ErrorKind::LengthOverflow {
+                length: len as u64,
+                max: length_prefix.max(),
+            })
+        };
+        match length_prefix {
+            LengthPrefix::U8 => {
+                let len = u8::try_from(len).map_err(|_| overflow())?;
+                self.write_u8(len)
+            }
+            LengthPrefix::U16 => {
+                let len = u16::try_from(len).map_err(|_| overflow())?;
+                self.write_u16(len)
+            }
+            LengthPrefix::U32 => {
+                let len = u32::try_from(len).map_err(|_| overflow())?;
+                self.write_u32(len)
+            }
+            LengthPrefix::U64 => {
+                let len = u64::try_from(len).map_err(|_| overflow())?;
+                self.write_u64(len)
+            }
+            LengthPrefix::Varint => {
+                let len = u64::try_from(len).map_err(|_| overflow())?;
+                self.write_varint(len.into())
+            }
+            LengthPrefix::QuicVarInt => {
+                let len = u64::try_from(len)
+                    .ok()
+                    .and_then(QuicVarInt::new)
+                    .ok_or_else(overflow)?;
+                let mut buf = [0; 8];
+                self.write_bytes(quic::encode(len.into_inner(), &mut buf))
+            }
+        }
+    }
+
+    fn serialize_discriminant(&mut self, variant_index: u32) -> Result<()> {
+        let discriminant = self.options.discriminant();
+        let overflow = |_| {
+            Error::from(ErrorKind::DiscriminantOverflow {
+                index: variant_index,
+                max: discriminant.max(),
+            })
+        };
+        match discriminant {
+            Discriminant::U8 => {
+                let index = u8::try_from(variant_index).map_err(overflow)?;
+                self.write_u8(index)
+            }
+            Discriminant::U16 => {
+                let index = u16::try_from(variant_index).map_err(overflow)?;
+                self.write_u16(index)
+            }
+            Discriminant::U32 => self.write_u32(variant_index),
+            Discriminant::Varint => self.write_varint(variant_index.into()),
+        }
+    }
 }
 
 /// # Errors
@@ -15,73 +155,174 @@ where
     W: Write,
     T: ?Sized + Serialize,
 {
-    let mut serializer = Serializer { output: writer };
+    to_writer_with_options(writer, value, Options::default())
+}
+
+/// # Errors
+pub fn to_writer_with_options<W, T>(writer: W, value: &T, options: Options) -> Result<()>
+where
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    let mut serializer = Serializer::with_options(writer, options);
     value.serialize(&mut serializer)
 }
 
+/// Serializes into `buf` and returns the part of it that was written.
+///
+/// # Errors
+///
+/// Returns [`ErrorKind::BufferTooSmall`] if the value does not fit.
+pub fn to_slice<'a, T>(value: &T, buf: &'a mut [u8]) -> Result<&'a mut [u8]>
+where
+    T: ?Sized + Serialize,
+{
+    to_slice_with_options(value, buf, Options::default())
+}
+
 /// # Errors
+pub fn to_slice_with_options<'a, T>(
+    value: &T,
+    buf: &'a mut [u8],
+    options: Options,
+) -> Result<&'a mut [u8]>
+where
+    T: ?Sized + Serialize,
+{
+    let mut serializer = Serializer::with_options(SliceWrite::new(buf), options);
+    value.serialize(&mut serializer)?;
+    Ok(serializer.into_inner().into_written())
+}
+
+/// # Errors
+#[cfg(feature = "alloc")]
 pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
 where
     T: Serialize,
 {
-    let mut serializer = Serializer { output: Vec::new() };
+    to_vec_with_options(value, Options::default())
+}
+
+/// # Errors
+#[cfg(feature = "alloc")]
+pub fn to_vec_with_options<T>(value: &T, options: Options) -> Result<Vec<u8>>
+where
+    T: Serialize,
+{
+    let mut serializer = Serializer::with_options(Vec::new(), options);
+    value.serialize(&mut serializer)?;
+    Ok(serializer.into_inner())
+}
+
+/// Number of bytes `value` encodes to, computed without allocating.
+///
+/// # Errors
+pub fn serialized_size<T>(value: &T) -> Result<u64>
+where
+    T: ?Sized + Serialize,
+{
+    serialized_size_with_options(value, Options::default())
+}
+
+/// # Errors
+pub fn serialized_size_with_options<T>(value: &T, options: Options) -> Result<u64>
+where
+    T: ?Sized + Serialize,
+{
+    let mut serializer = Serializer::with_options(SizeCounter, options);
     value.serialize(&mut serializer)?;
-    Ok(serializer.output)
+    Ok(serializer.bytes_written())
 }
 
-impl<W: Write> ser::Serializer for &mut Serializer<W> {
+impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
     type Ok = ();
     type Error = Error;
-    type SerializeSeq = Self;
-    type SerializeTuple = Self;
-    type SerializeTupleStruct = Self;
-    type SerializeTupleVariant = Self;
-    type SerializeMap = Self;
-    type SerializeStruct = Self;
-    type SerializeStructVariant = Self;
+    type SerializeSeq = Compound<'a, Serializer<W>>;
+    type SerializeTuple = Compound<'a, Serializer<W>>;
+    type SerializeTupleStruct = Compound<'a, Serializer<W>>;
+    type SerializeTupleVariant = Compound<'a, Serializer<W>>;
+    type SerializeMap = Compound<'a, Serializer<W>>;
+    type SerializeStruct = Compound<'a, Serializer<W>>;
+    type SerializeStructVariant = Compound<'a, Serializer<W>>;
+
+    fn is_human_readable(&self) -> bool {
+        self.options.human_readable()
+    }
 
     fn serialize_bool(self, v: bool) -> Result<()> {
-        self.output.write_u8(u8::from(v)).map_err(Error::io)
+        self.write_u8(u8::from(v))
     }
 
     fn serialize_i8(self, v: i8) -> Result<()> {
-        self.output.write_i8(v).map_err(Error::io)
+        self.write_i8(v)
     }
 
     fn serialize_i16(self, v: i16) -> Result<()> {
-        self.output.write_i16::<NetworkEndian>(v).map_err(Error::io)
+        match self.options.int_encoding() {
+            IntEncoding::Fixed => self.write_i16(v),
+            IntEncoding::Varint => self.write_varint(varint::zigzag(v.into())),
+        }
     }
 
     fn serialize_i32(self, v: i32) -> Result<()> {
-        self.output.write_i32::<NetworkEndian>(v).map_err(Error::io)
+        match self.options.int_encoding() {
+            IntEncoding::Fixed => self.write_i32(v),
+            IntEncoding::Varint => self.write_varint(varint::zigzag(v.into())),
+        }
     }
 
     fn serialize_i64(self, v: i64) -> Result<()> {
-        self.output.write_i64::<NetworkEndian>(v).map_err(Error::io)
+        match self.options.int_encoding() {
+            IntEncoding::Fixed => self.write_i64(v),
+            IntEncoding::Varint => self.write_varint(varint::zigzag(v.into())),
+        }
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        match self.options.int_encoding() {
+            IntEncoding::Fixed => self.write_i128(v),
+            IntEncoding::Varint => self.write_varint(varint::zigzag(v)),
+        }
     }
 
     fn serialize_u8(self, v: u8) -> Result<()> {
-        self.output.write_u8(v).map_err(Error::io)
+        self.write_u8(v)
     }
 
     fn serialize_u16(self, v: u16) -> Result<()> {
-        self.output.write_u16::<NetworkEndian>(v).map_err(Error::io)
+        match self.options.int_encoding() {
+            IntEncoding::Fixed => self.write_u16(v),
+            IntEncoding::Varint => self.write_varint(v.into()),
+        }
     }
 
     fn serialize_u32(self, v: u32) -> Result<()> {
-        self.output.write_u32::<NetworkEndian>(v).map_err(Error::io)
+        match self.options.int_encoding() {
+            IntEncoding::Fixed => self.write_u32(v),
+            IntEncoding::Varint => self.write_varint(v.into()),
+        }
     }
 
     fn serialize_u64(self, v: u64) -> Result<()> {
-        self.output.write_u64::<NetworkEndian>(v).map_err(Error::io)
+        match self.options.int_encoding() {
+            IntEncoding::Fixed => self.write_u64(v),
+            IntEncoding::Varint => self.write_varint(v.into()),
+        }
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        match self.options.int_encoding() {
+            IntEncoding::Fixed => self.write_u128(v),
+            IntEncoding::Varint => self.write_varint(v),
+        }
     }
 
     fn serialize_f32(self, v: f32) -> Result<()> {
-        self.output.write_f32::<NetworkEndian>(v).map_err(Error::io)
+        self.write_f32(v)
     }
 
     fn serialize_f64(self, v: f64) -> Result<()> {
-        self.output.write_f64::<NetworkEndian>(v).map_err(Error::io)
+        self.write_f64(v)
     }
 
     fn serialize_char(self, v: char) -> Result<()> {
@@ -92,21 +333,26 @@ impl<W: Write> ser::Serializer for &mut Serializer<W> {
         self.serialize_bytes(v.as_bytes())
     }
 
-    #[allow(clippy::cast_possible_truncation)]
     fn serialize_bytes(self, v: &[u8]) -> Result<()> {
-        self.output
-            .write_u16::<NetworkEndian>(v.len() as u16)
-            .map_err(Error::io)?;
-        self.output.write_all(v).map_err(Error::io)
+        self.serialize_length(v.len())?;
+        self.write_bytes(v)
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn collect_str<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + fmt::Display,
+    {
+        collect_str_unbuffered(self, value)
     }
 
     fn serialize_none(self) -> Result<()> {
         self.serialize_bool(false)
     }
 
-    fn serialize_some<T: ?Sized>(self, value: &T) -> Result<()>
+    fn serialize_some<T>(self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         self.serialize_bool(true)?;
         value.serialize(&mut *self)
@@ -120,63 +366,59 @@ impl<W: Write> ser::Serializer for &mut Serializer<W> {
         Ok(())
     }
 
-    #[allow(clippy::cast_possible_truncation)]
     fn serialize_unit_variant(
         self,
         _name: &'static str,
         variant_index: u32,
         _variant: &'static str,
     ) -> Result<()> {
-        self.output.write_u8(variant_index as u8).map_err(Error::io)
+        self.serialize_discriminant(variant_index)
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(self, _name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         value.serialize(&mut *self)
     }
 
-    #[allow(clippy::cast_possible_truncation)]
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
-        _name: &'static str,
+        name: &'static str,
         variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         value: &T,
     ) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        self.output
-            .write_u8(variant_index as u8)
-            .map_err(Error::io)?;
-        value.serialize(&mut *self)
+        self.serialize_discriminant(variant_index)?;
+        value.serialize(&mut *self).map_err(|err| {
+            err.in_segment(Segment::Variant(variant))
+                .in_segment(Segment::Type(name))
+        })
     }
 
-    #[allow(clippy::cast_possible_truncation)]
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
         match len {
             Some(len) => {
-                self.output
-                    .write_u16::<NetworkEndian>(len as u16)
-                    .map_err(Error::io)?;
-                Ok(self)
+                self.serialize_length(len)?;
+                Ok(Compound::new(self, None, None))
             }
-            None => Err(Error::LengthNotKnown),
+            None => Err(ErrorKind::LengthNotKnown.into()),
         }
     }
 
     fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
-        Ok(self)
+        Ok(Compound::new(self, None, None))
     }
 
     fn serialize_tuple_struct(
         self,
-        _name: &'static str,
+        name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        Ok(self)
+        Ok(Compound::new(self, Some(name), None))
     }
 
     fn serialize_tuple_variant(
@@ -193,34 +435,163 @@ impl<W: Write> ser::Serializer for &mut Serializer<W> {
         self.serialize_seq(len)
     }
 
-    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
-        Ok(self)
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Ok(Compound::new(self, Some(name), None))
     }
 
-    #[allow(clippy::cast_possible_truncation)]
     fn serialize_struct_variant(
         self,
-        _name: &'static str,
+        name: &'static str,
         variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        self.output
-            .write_u8(variant_index as u8)
-            .map_err(Error::io)?;
-        Ok(self)
+        self.serialize_discriminant(variant_index)?;
+        Ok(Compound::new(self, Some(name), Some(variant)))
     }
 }
 
-impl<W: Write> ser::SerializeSeq for &mut Serializer<W> {
+/// Length prefix and raw bytes of a string, for [`collect_str_unbuffered`].
+#[cfg(not(feature = "alloc"))]
+pub(crate) trait WriteStr {
+    fn serialize_length(&mut self, len: usize) -> Result<()>;
+    fn write_str_bytes(&mut self, bytes: &[u8]) -> Result<()>;
+}
+
+#[cfg(not(feature = "alloc"))]
+impl<W: Write> WriteStr for Serializer<W> {
+    fn serialize_length(&mut self, len: usize) -> Result<()> {
+        Serializer::serialize_length(self, len)
+    }
+
+    fn write_str_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.write_bytes(bytes)
+    }
+}
+
+/// Serializes the `Display` output of `value` as a string without buffering
+/// it, by formatting it twice: first to measure it for the length prefix.
+#[cfg(not(feature = "alloc"))]
+pub(crate) fn collect_str_unbuffered<S, T>(ser: &mut S, value: &T) -> Result<()>
+where
+    S: WriteStr,
+    T: ?Sized + fmt::Display,
+{
+    let display_error =
+        || <Error as ser::Error>::custom("a Display implementation returned an error");
+    let mut counter = FmtCounter(0);
+    fmt::write(&mut counter, format_args!("{}", value)).map_err(|_| display_error())?;
+    ser.serialize_length(counter.0)?;
+    let mut writer = FmtWriter {
+        ser,
+        written: 0,
+        error: None,
+    };
+    if fmt::write(&mut writer, format_args!("{}", value)).is_err() {
+        return Err(writer.error.unwrap_or_else(display_error));
+    }
+    if writer.written == counter.0 {
+        Ok(())
+    } else {
+        Err(display_error())
+    }
+}
+
+/// Counts the length of a formatted string for `collect_str`.
+#[cfg(not(feature = "alloc"))]
+struct FmtCounter(usize);
+
+#[cfg(not(feature = "alloc"))]
+impl fmt::Write for FmtCounter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+/// Writes a formatted string for `collect_str`, keeping the first error.
+#[cfg(not(feature = "alloc"))]
+struct FmtWriter<'a, S> {
+    ser: &'a mut S,
+    written: usize,
+    error: Option<Error>,
+}
+
+#[cfg(not(feature = "alloc"))]
+impl<'a, S: WriteStr> fmt::Write for FmtWriter<'a, S> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.written += s.len();
+        self.ser.write_str_bytes(s.as_bytes()).map_err(|err| {
+            self.error = Some(err);
+            fmt::Error
+        })
+    }
+}
+
+/// State for serializing the elements of a compound value, used to record
+/// where in the value an error happened.
+pub struct Compound<'a, S> {
+    ser: &'a mut S,
+    name: Option<&'static str>,
+    variant: Option<&'static str>,
+    index: usize,
+}
+
+impl<'a, S> Compound<'a, S>
+where
+    for<'b> &'b mut S: ser::Serializer<Ok = (), Error = Error>,
+{
+    pub(crate) fn new(
+        ser: &'a mut S,
+        name: Option<&'static str>,
+        variant: Option<&'static str>,
+    ) -> Self {
+        Compound {
+            ser,
+            name,
+            variant,
+            index: 0,
+        }
+    }
+
+    fn serialize_in<T>(&mut self, segment: Segment, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut *self.ser).map_err(|mut err| {
+            err = err.in_segment(segment);
+            if let Some(variant) = self.variant {
+                err = err.in_segment(Segment::Variant(variant));
+            }
+            if let Some(name) = self.name {
+                err = err.in_segment(Segment::Type(name));
+            }
+            err
+        })
+    }
+
+    fn serialize_next<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let segment = Segment::Index(self.index);
+        self.index += 1;
+        self.serialize_in(segment, value)
+    }
+}
+
+impl<'a, S> ser::SerializeSeq for Compound<'a, S>
+where
+    for<'b> &'b mut S: ser::Serializer<Ok = (), Error = Error>,
+{
     type Ok = ();
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        self.serialize_next(value)
     }
 
     fn end(self) -> Result<()> {
@@ -228,15 +599,18 @@ impl<W: Write> ser::SerializeSeq for &mut Serializer<W> {
     }
 }
 
-impl<W: Write> ser::SerializeTuple for &mut Serializer<W> {
+impl<'a, S> ser::SerializeTuple for Compound<'a, S>
+where
+    for<'b> &'b mut S: ser::Serializer<Ok = (), Error = Error>,
+{
     type Ok = ();
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        self.serialize_next(value)
     }
 
     fn end(self) -> Result<()> {
@@ -244,15 +618,18 @@ impl<W: Write> ser::SerializeTuple for &mut Serializer<W> {
     }
 }
 
-impl<W: Write> ser::SerializeTupleStruct for &mut Serializer<W> {
+impl<'a, S> ser::SerializeTupleStruct for Compound<'a, S>
+where
+    for<'b> &'b mut S: ser::Serializer<Ok = (), Error = Error>,
+{
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        self.serialize_next(value)
     }
 
     fn end(self) -> Result<()> {
@@ -260,15 +637,18 @@ impl<W: Write> ser::SerializeTupleStruct for &mut Serializer<W> {
     }
 }
 
-impl<W: Write> ser::SerializeTupleVariant for &mut Serializer<W> {
+impl<'a, S> ser::SerializeTupleVariant for Compound<'a, S>
+where
+    for<'b> &'b mut S: ser::Serializer<Ok = (), Error = Error>,
+{
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        self.serialize_next(value)
     }
 
     fn end(self) -> Result<()> {
@@ -276,22 +656,27 @@ impl<W: Write> ser::SerializeTupleVariant for &mut Serializer<W> {
     }
 }
 
-impl<W: Write> ser::SerializeMap for &mut Serializer<W> {
+impl<'a, S> ser::SerializeMap for Compound<'a, S>
+where
+    for<'b> &'b mut S: ser::Serializer<Ok = (), Error = Error>,
+{
     type Ok = ();
     type Error = Error;
 
-    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<()>
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        key.serialize(&mut **self)
+        self.serialize_in(Segment::MapKey(self.index), key)
     }
 
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        let segment = Segment::MapValue(self.index);
+        self.index += 1;
+        self.serialize_in(segment, value)
     }
 
     fn end(self) -> Result<()> {
@@ -299,15 +684,18 @@ impl<W: Write> ser::SerializeMap for &mut Serializer<W> {
     }
 }
 
-impl<W: Write> ser::SerializeStruct for &mut Serializer<W> {
+impl<'a, S> ser::SerializeStruct for Compound<'a, S>
+where
+    for<'b> &'b mut S: ser::Serializer<Ok = (), Error = Error>,
+{
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        self.serialize_in(Segment::Field(key), value)
     }
 
     fn end(self) -> Result<()> {
@@ -315,15 +703,18 @@ impl<W: Write> ser::SerializeStruct for &mut Serializer<W> {
     }
 }
 
-impl<W: Write> ser::SerializeStructVariant for &mut Serializer<W> {
+impl<'a, S> ser::SerializeStructVariant for Compound<'a, S>
+where
+    for<'b> &'b mut S: ser::Serializer<Ok = (), Error = Error>,
+{
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        self.serialize_in(Segment::Field(key), value)
     }
 
     fn end(self) -> Result<()> {
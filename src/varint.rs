@@ -0,0 +1,58 @@
+//! Unsigned LEB128: seven bits per byte, least significant group first, with
+//! the high bit set on every byte except the last. Signed integers are ZigZag
+//! encoded first so that small magnitudes stay short.
+
+use crate::error::{ErrorKind, Result};
+
+/// Longest encoding, that of `u128::MAX`.
+pub(crate) const MAX_LEN: usize = 19;
+
+pub(crate) fn encode(mut value: u128, buf: &mut [u8; MAX_LEN]) -> &[u8] {
+    let mut len = 0;
+    loop {
+        #[allow(clippy::cast_possible_truncation)]
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf[len] = byte;
+            return &buf[..=len];
+        }
+        buf[len] = byte | 0x80;
+        len += 1;
+    }
+}
+
+/// Decodes a value that must fit in `bits` bits, rejecting encodings that
+/// are longer than necessary.
+pub(crate) fn decode<F>(mut next_byte: F, bits: u32) -> Result<u128>
+where
+    F: FnMut() -> Result<u8>,
+{
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = next_byte()?;
+        let group = u128::from(byte & 0x7f);
+        if shift >= bits || (bits - shift < 7 && group >> (bits - shift) != 0) {
+            return Err(ErrorKind::VarintOverflow.into());
+        }
+        value |= group << shift;
+        if byte & 0x80 == 0 {
+            if byte == 0 && shift > 0 {
+                return Err(ErrorKind::VarintOverlong.into());
+            }
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+#[allow(clippy::cast_sign_loss)]
+pub(crate) const fn zigzag(value: i128) -> u128 {
+    ((value << 1) ^ (value >> 127)) as u128
+}
+
+#[allow(clippy::cast_possible_wrap)]
+pub(crate) const fn unzigzag(value: u128) -> i128 {
+    (value >> 1) as i128 ^ -((value & 1) as i128)
+}
@@ -0,0 +1,110 @@
+//! Output sinks for [`Serializer`](crate::Serializer).
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io;
+
+#[cfg(feature = "std")]
+use crate::error::Error;
+use crate::error::{ErrorKind, Result};
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// A sink for the bytes produced by the [`Serializer`](crate::Serializer).
+///
+/// This trait is sealed; it is implemented by every `std::io::Write`, by
+/// `Vec<u8>` and by [`SliceWrite`].
+pub trait Write: private::Sealed {
+    #[doc(hidden)]
+    fn write_all(&mut self, buf: &[u8]) -> Result<()>;
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write> private::Sealed for W {}
+
+#[cfg(feature = "std")]
+impl<W: io::Write> Write for W {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        io::Write::write_all(self, buf).map_err(Error::io)
+    }
+}
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+impl private::Sealed for Vec<u8> {}
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+impl Write for Vec<u8> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<W: Write + ?Sized> private::Sealed for &mut W {}
+
+#[cfg(not(feature = "std"))]
+impl<W: Write + ?Sized> Write for &mut W {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        (**self).write_all(buf)
+    }
+}
+
+// With `std`, `&mut W` is covered by the `io::Write` impl for the writers
+// that implement it, which `SliceWrite` does not.
+#[cfg(feature = "std")]
+impl<'a, 'b> private::Sealed for &'b mut SliceWrite<'a> {}
+
+#[cfg(feature = "std")]
+impl<'a, 'b> Write for &'b mut SliceWrite<'a> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        (**self).write_all(buf)
+    }
+}
+
+/// Writes into a fixed byte slice, failing with
+/// [`ErrorKind::BufferTooSmall`] once it is full.
+pub struct SliceWrite<'a> {
+    slice: &'a mut [u8],
+    index: usize,
+}
+
+impl<'a> SliceWrite<'a> {
+    pub fn new(slice: &'a mut [u8]) -> Self {
+        SliceWrite { slice, index: 0 }
+    }
+
+    /// Returns the part of the slice that has been written to.
+    pub fn into_written(self) -> &'a mut [u8] {
+        &mut self.slice[..self.index]
+    }
+}
+
+impl<'a> private::Sealed for SliceWrite<'a> {}
+
+impl<'a> Write for SliceWrite<'a> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        let rest = &mut self.slice[self.index..];
+        if buf.len() > rest.len() {
+            return Err(ErrorKind::BufferTooSmall.into());
+        }
+        rest[..buf.len()].copy_from_slice(buf);
+        self.index += buf.len();
+        Ok(())
+    }
+}
+
+/// Discards its input; the [`Serializer`](crate::Serializer) keeps count of
+/// the bytes.
+pub(crate) struct SizeCounter;
+
+impl private::Sealed for SizeCounter {}
+
+impl Write for SizeCounter {
+    fn write_all(&mut self, _buf: &[u8]) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+use proptest::collection::{btree_map, vec};
+use proptest::prelude::*;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use serde_net::{
+    from_bytes_with_options, from_reader_with_options, from_slice_with_options,
+    serialized_size_with_options, to_vec_with_options, Deserializer, Discriminant, Endian,
+    IntEncoding, LengthPrefix, Options, Serializer, StreamDeserializer,
+};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Tree {
+    Leaf,
+    Node(Box<Tree>),
+    Branch(Vec<Tree>),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+enum Kind {
+    Empty,
+    Code(i16),
+    Pair(u8, i8),
+    Point { x: f32, y: f32 },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Message {
+    id: u32,
+    flag: bool,
+    name: String,
+    payload: ByteBuf,
+    values: Vec<i64>,
+    tag: Option<char>,
+    kinds: Vec<Kind>,
+    table: BTreeMap<u16, String>,
+    ratio: f64,
+}
+
+fn options() -> impl Strategy<Value = Options> {
+    let length_prefix = prop_oneof![
+        Just(LengthPrefix::U8),
+        Just(LengthPrefix::U16),
+        Just(LengthPrefix::U32),
+        Just(LengthPrefix::U64),
+        Just(LengthPrefix::Varint),
+        Just(LengthPrefix::QuicVarInt),
+    ];
+    let discriminant = prop_oneof![
+        Just(Discriminant::U8),
+        Just(Discriminant::U16),
+        Just(Discriminant::U32),
+        Just(Discriminant::Varint),
+    ];
+    let endian = prop_oneof![
+        Just(Endian::Big),
+        Just(Endian::Little),
+        Just(Endian::Native)
+    ];
+    let int_encoding = prop_oneof![Just(IntEncoding::Fixed), Just(IntEncoding::Varint)];
+    (
+        length_prefix,
+        discriminant,
+        endian,
+        int_encoding,
+        any::<bool>(),
+    )
+        .prop_map(
+            |(length_prefix, discriminant, endian, int_encoding, strict)| {
+                Options::new()
+                    .with_length_prefix(length_prefix)
+                    .with_discriminant(discriminant)
+                    .with_endian(endian)
+                    .with_int_encoding(int_encoding)
+                    .with_strict(strict)
+            },
+        )
+}
+
+fn kind() -> impl Strategy<Value = Kind> {
+    let float = proptest::num::f32::NORMAL | proptest::num::f32::ZERO;
+    prop_oneof![
+        Just(Kind::Empty),
+        any::<i16>().prop_map(Kind::Code),
+        (any::<u8>(), any::<i8>()).prop_map(|(a, b)| Kind::Pair(a, b)),
+        (float, float).prop_map(|(x, y)| Kind::Point { x, y }),
+    ]
+}
+
+fn message() -> impl Strategy<Value = Message> {
+    (
+        (any::<u32>(), any::<bool>(), ".{0,20}"),
+        vec(any::<u8>(), 0..64),
+        vec(any::<i64>(), 0..16),
+        any::<Option<char>>(),
+        vec(kind(), 0..8),
+        btree_map(any::<u16>(), ".{0,8}", 0..8),
+        proptest::num::f64::NORMAL | proptest::num::f64::ZERO,
+    )
+        .prop_map(
+            |((id, flag, name), payload, values, tag, kinds, table, ratio)| Message {
+                id,
+                flag,
+                name,
+                payload: ByteBuf::from(payload),
+                values,
+                tag,
+                kinds,
+                table,
+                ratio,
+            },
+        )
+}
+
+proptest! {
+    #[test]
+    fn roundtrip_message(value in message(), options in options()) {
+        let mut bytes = to_vec_with_options(&value, options).unwrap();
+        prop_assert_eq!(serialized_size_with_options(&value, options).unwrap(), bytes.len() as u64);
+        let from_reader: Message = from_reader_with_options(bytes.as_slice(), options).unwrap();
+        prop_assert_eq!(&from_reader, &value);
+        let from_slice: Message = from_slice_with_options(&bytes, options).unwrap();
+        prop_assert_eq!(&from_slice, &value);
+        let from_bytes: Message = from_bytes_with_options(&mut bytes, options).unwrap();
+        prop_assert_eq!(from_bytes, value);
+    }
+
+    #[test]
+    fn roundtrip_stream(values in vec(message(), 0..4), options in options()) {
+        let mut serializer = Serializer::with_options(Vec::new(), options);
+        for value in &values {
+            value.serialize(&mut serializer).unwrap();
+        }
+        let bytes = serializer.into_inner();
+        let de = Deserializer::from_reader_with_options(bytes.as_slice(), options);
+        let decoded = StreamDeserializer::new(de).collect::<Result<Vec<Message>, _>>().unwrap();
+        prop_assert_eq!(decoded, values);
+    }
+
+    #[test]
+    fn garbage_does_not_panic(mut bytes in vec(any::<u8>(), 0..256), options in options()) {
+        if let Err(err) = from_bytes_with_options::<_, Message>(&mut bytes, options) {
+            prop_assert!(err.offset().is_some());
+        }
+        let _ = from_bytes_with_options::<_, Vec<Vec<String>>>(&mut bytes, options);
+        let _ = from_bytes_with_options::<_, Option<Kind>>(&mut bytes, options);
+        let _ = from_bytes_with_options::<_, BTreeMap<String, u64>>(&mut bytes, options);
+        let _ = from_bytes_with_options::<_, (char, bool, f64, Box<str>)>(&mut bytes, options);
+        let _ = from_bytes_with_options::<_, IgnoredAny>(&mut bytes, options);
+        let _ = from_bytes_with_options::<_, Tree>(&mut bytes, options);
+        let mut deep = vec![1; 1 << 20];
+        deep.extend_from_slice(&bytes);
+        let _ = from_bytes_with_options::<_, Tree>(&mut deep, options);
+        if let Err(err) = from_reader_with_options::<_, Message>(bytes.as_slice(), options) {
+            prop_assert!(err.offset().is_some());
+        }
+        let _ = from_slice_with_options::<(&str, &[u8], Vec<&str>)>(&bytes, options);
+    }
+
+    #[test]
+    fn truncated_input_is_an_error(value in message(), options in options(), cut in any::<prop::sample::Index>()) {
+        let bytes = to_vec_with_options(&value, options).unwrap();
+        let mut truncated = bytes[..cut.index(bytes.len())].to_vec();
+        let result = from_bytes_with_options::<_, Message>(&mut truncated, options);
+        prop_assert!(result.is_err());
+    }
+}
@@ -1,9 +1,19 @@
 use serde::{de, ser, Deserialize, Serialize};
 use serde_bytes::{ByteBuf, Bytes};
-use serde_net::{from_bytes, to_vec, Error};
+use serde_net::bits::{self, BitOptions, BitOrder, Padding};
+use serde_net::{
+    from_bytes, from_bytes_with_options, from_reader, from_reader_with_options, from_slice,
+    from_slice_with_options, serialized_size, serialized_size_with_options, take_from_bytes,
+    take_from_bytes_with_options, take_from_slice, to_slice, to_slice_with_options, to_vec,
+    to_vec_with_options, Deserializer, Discriminant, Endian, Error, ErrorKind, IntEncoding,
+    LengthPrefix, Limits, Options, QuicVarInt, Segment, Serializer, StreamDeserializer, I24, U24,
+    U48,
+};
+use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::ffi::CString;
 use std::fmt::Debug;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 
 fn test_roundtrip_ok<T>(value: T, output: Vec<u8>)
 where
@@ -16,6 +26,17 @@ where
     assert_eq!(v, value);
 }
 
+fn test_roundtrip_with_options_ok<T>(value: T, options: Options, output: Vec<u8>)
+where
+    T: PartialEq + Debug + ser::Serialize + de::DeserializeOwned,
+{
+    let mut bytes = to_vec_with_options(&value, options).unwrap();
+    assert_eq!(bytes, output);
+
+    let v: T = from_bytes_with_options(&mut bytes, options).unwrap();
+    assert_eq!(v, value);
+}
+
 #[test]
 fn test_roundtrip_unit() {
     test_roundtrip_ok((), vec![]);
@@ -63,6 +84,21 @@ fn test_roundtrip_i64() {
     test_roundtrip_ok(i64::MAX, vec![127, 255, 255, 255, 255, 255, 255, 255]);
 }
 
+#[test]
+fn test_roundtrip_i128() {
+    let mut output = vec![255; 16];
+    output[15] = 254;
+    test_roundtrip_ok(-2i128, output);
+    test_roundtrip_ok(0i128, vec![0; 16]);
+    test_roundtrip_ok(
+        0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10i128,
+        (1..=16).collect(),
+    );
+    let mut output = vec![0; 16];
+    output[0] = 128;
+    test_roundtrip_ok(i128::MIN, output);
+}
+
 #[test]
 fn test_roundtrip_u8() {
     test_roundtrip_ok(3u8, vec![3]);
@@ -91,6 +127,23 @@ fn test_roundtrip_u64() {
     test_roundtrip_ok(u64::MAX, vec![255, 255, 255, 255, 255, 255, 255, 255]);
 }
 
+#[test]
+fn test_roundtrip_u128() {
+    test_roundtrip_ok(
+        0x2001_0db8_0000_0000_0000_0000_0000_0001u128,
+        vec![0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+    );
+    test_roundtrip_ok(u128::MIN, vec![0; 16]);
+    test_roundtrip_ok(u128::MAX, vec![255; 16]);
+
+    let options = Options::new().with_endian(Endian::Little);
+    test_roundtrip_with_options_ok(1u128, options, {
+        let mut output = vec![0; 16];
+        output[0] = 1;
+        output
+    });
+}
+
 #[test]
 fn test_roundtrip_f32() {
     test_roundtrip_ok(-1.333f32, vec![191, 170, 159, 190]);
@@ -203,6 +256,33 @@ fn test_roundtrip_struct() {
     test_roundtrip_ok(value, vec![0, 0, 0, 1, 0, 2, 0, 1, 97, 0, 1, 98]);
 }
 
+#[test]
+fn test_roundtrip_length_prefix_u8() {
+    let options = Options::new().with_length_prefix(LengthPrefix::U8);
+    test_roundtrip_with_options_ok(String::from("ab"), options, vec![2, 97, 98]);
+    test_roundtrip_with_options_ok(vec![7u8, 9], options, vec![2, 7, 9]);
+}
+
+#[test]
+fn test_roundtrip_length_prefix_u32() {
+    let options = Options::new().with_length_prefix(LengthPrefix::U32);
+    test_roundtrip_with_options_ok(String::from("ab"), options, vec![0, 0, 0, 2, 97, 98]);
+
+    let mut map: BTreeMap<u8, u8> = BTreeMap::new();
+    map.insert(1, 2);
+    test_roundtrip_with_options_ok(map, options, vec![0, 0, 0, 1, 1, 2]);
+}
+
+#[test]
+fn test_roundtrip_length_prefix_u64() {
+    let options = Options::new().with_length_prefix(LengthPrefix::U64);
+    test_roundtrip_with_options_ok(
+        vec![String::from("a")],
+        options,
+        vec![0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 97],
+    );
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 enum E {
     Unit,
@@ -243,8 +323,8 @@ fn test_deserialize_byte_buf() {
 
 #[test]
 fn test_serialize_bytes() {
-    let mut bytes = Bytes::new(b"Hello");
-    let result = to_vec(&mut bytes).unwrap();
+    let bytes = Bytes::new(b"Hello");
+    let result = to_vec(&bytes).unwrap();
     assert_eq!(result, vec![0, 5, 72, 101, 108, 108, 111]);
 }
 
@@ -252,38 +332,1502 @@ fn test_serialize_bytes() {
 fn test_deserialize_string_trailing_bytes() {
     let mut value = vec![0, 2, 72, 101, 108];
     let result: Result<String, Error> = from_bytes(&mut value);
-    match result {
-        Err(Error::TrailingBytes) => {}
-        _ => assert!(false),
-    }
+    assert!(matches!(
+        result.unwrap_err().kind(),
+        ErrorKind::TrailingBytes
+    ));
 }
 
 #[test]
 fn test_deserialize_map_trailing_bytes() {
     let mut value = vec![0, 2, 72, 101, 108, 77, 67];
     let result: Result<BTreeMap<u8, u8>, Error> = from_bytes(&mut value);
-    match result {
-        Err(Error::TrailingBytes) => {}
-        _ => assert!(false),
-    }
+    assert!(matches!(
+        result.unwrap_err().kind(),
+        ErrorKind::TrailingBytes
+    ));
 }
 
 #[test]
 fn test_deserialize_string_eof_while_deserializing() {
     let mut value = vec![0, 4, 72, 101, 108];
     let result: Result<String, Error> = from_bytes(&mut value);
-    match result {
-        Err(Error::EofWhileDeserializing) => {}
-        _ => assert!(false),
-    }
+    assert!(matches!(
+        result.unwrap_err().kind(),
+        ErrorKind::EofWhileDeserializing
+    ));
 }
 
 #[test]
 fn test_deserialize_map_eof_while_deserializing() {
     let mut value = vec![0, 3, 72, 101, 108, 77, 67];
     let result: Result<BTreeMap<u8, u8>, Error> = from_bytes(&mut value);
-    match result {
-        Err(Error::EofWhileDeserializing) => {}
-        _ => assert!(false),
+    assert!(matches!(
+        result.unwrap_err().kind(),
+        ErrorKind::EofWhileDeserializing
+    ));
+}
+
+#[test]
+fn test_serialize_bytes_length_overflow() {
+    let bytes = ByteBuf::from(vec![0; 70_000]);
+    let result = to_vec(&bytes);
+    assert!(matches!(
+        result.unwrap_err().kind(),
+        ErrorKind::LengthOverflow {
+            length: 70_000,
+            max: 65_535
+        }
+    ));
+}
+
+#[test]
+fn test_serialize_seq_length_overflow() {
+    let options = Options::new().with_length_prefix(LengthPrefix::U8);
+    let result = to_vec_with_options(&vec![0u8; 256], options);
+    assert!(matches!(
+        result.unwrap_err().kind(),
+        ErrorKind::LengthOverflow {
+            length: 256,
+            max: 255
+        }
+    ));
+
+    let result = to_vec_with_options(&vec![0u8; 255], options);
+    assert_eq!(result.unwrap().len(), 256);
+}
+
+#[test]
+fn test_roundtrip_large_array() {
+    let value = vec![7u8; 70_000];
+    let options = Options::new().with_length_prefix(LengthPrefix::U32);
+    let mut bytes = to_vec_with_options(&value, options).unwrap();
+    assert_eq!(bytes[..4], [0, 1, 17, 112]);
+
+    let v: Vec<u8> = from_bytes_with_options(&mut bytes, options).unwrap();
+    assert_eq!(v, value);
+}
+
+#[test]
+fn test_roundtrip_discriminant_widths() {
+    let options = Options::new().with_discriminant(Discriminant::U16);
+    test_roundtrip_with_options_ok(E::Newtype(1), options, vec![0, 1, 0, 0, 0, 1]);
+
+    let options = Options::new().with_discriminant(Discriminant::U32);
+    test_roundtrip_with_options_ok(E::Tuple(1, 2), options, vec![0, 0, 0, 2, 1, 2]);
+
+    let options = Options::new().with_discriminant(Discriminant::Varint);
+    test_roundtrip_with_options_ok(E::Struct { a: 1 }, options, vec![3, 0, 0, 0, 1]);
+}
+
+struct Variant(u32);
+
+impl ser::Serialize for Variant {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_unit_variant("Variant", self.0, "V")
+    }
+}
+
+#[test]
+fn test_serialize_discriminant_overflow() {
+    let result = to_vec(&Variant(256));
+    assert!(matches!(
+        result.unwrap_err().kind(),
+        ErrorKind::DiscriminantOverflow {
+            index: 256,
+            max: 255
+        }
+    ));
+
+    let options = Options::new().with_discriminant(Discriminant::U16);
+    let result = to_vec_with_options(&Variant(65_536), options);
+    assert!(matches!(
+        result.unwrap_err().kind(),
+        ErrorKind::DiscriminantOverflow {
+            index: 65_536,
+            max: 65_535
+        }
+    ));
+}
+
+#[test]
+fn test_serialize_discriminant_varint() {
+    let options = Options::new().with_discriminant(Discriminant::Varint);
+    let result = to_vec_with_options(&Variant(300), options).unwrap();
+    assert_eq!(result, vec![0xac, 0x02]);
+
+    let result = to_vec_with_options(&Variant(u32::MAX), options).unwrap();
+    assert_eq!(result, vec![0xff, 0xff, 0xff, 0xff, 0x0f]);
+}
+
+#[test]
+fn test_deserialize_discriminant_varint_overflow() {
+    let options = Options::new().with_discriminant(Discriminant::Varint);
+    let mut input = vec![0xff, 0xff, 0xff, 0xff, 0x10];
+    let result: Result<E, Error> = from_bytes_with_options(&mut input, options);
+    assert!(matches!(
+        result.unwrap_err().kind(),
+        ErrorKind::VarintOverflow
+    ));
+}
+
+#[test]
+fn test_roundtrip_little_endian() {
+    let options = Options::new().with_endian(Endian::Little);
+    test_roundtrip_with_options_ok(5456u16, options, vec![80, 21]);
+    test_roundtrip_with_options_ok(-7359i32, options, vec![65, 227, 255, 255]);
+    test_roundtrip_with_options_ok(9764533u64, options, vec![181, 254, 148, 0, 0, 0, 0, 0]);
+    test_roundtrip_with_options_ok(1.333f32, options, vec![190, 159, 170, 63]);
+    test_roundtrip_with_options_ok(
+        -78.42568f64,
+        options,
+        vec![236, 163, 83, 87, 62, 155, 83, 192],
+    );
+    test_roundtrip_with_options_ok('💯', options, vec![175, 244, 1, 0]);
+    test_roundtrip_with_options_ok(String::from("ab"), options, vec![2, 0, 97, 98]);
+
+    let options = options.with_discriminant(Discriminant::U16);
+    test_roundtrip_with_options_ok(E::Newtype(1), options, vec![1, 0, 1, 0, 0, 0]);
+}
+
+#[test]
+fn test_roundtrip_native_endian() {
+    let options = Options::new().with_endian(Endian::Native);
+    test_roundtrip_with_options_ok(7359u32, options, 7359u32.to_ne_bytes().to_vec());
+    test_roundtrip_with_options_ok(-3456i16, options, (-3456i16).to_ne_bytes().to_vec());
+}
+
+#[test]
+fn test_roundtrip_field_byte_order() {
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Header {
+        length: u16,
+        #[serde(with = "serde_net::le")]
+        counter: u32,
+        #[serde(with = "serde_net::le")]
+        offset: i64,
+        #[serde(with = "serde_net::le")]
+        ratio: f32,
+    }
+
+    let value = Header {
+        length: 258,
+        counter: 7359,
+        offset: -2,
+        ratio: 1.333,
+    };
+    test_roundtrip_ok(
+        value.clone(),
+        vec![
+            1, 2, 191, 28, 0, 0, 254, 255, 255, 255, 255, 255, 255, 255, 190, 159, 170, 63,
+        ],
+    );
+
+    let options = Options::new().with_endian(Endian::Little);
+    test_roundtrip_with_options_ok(
+        value,
+        options,
+        vec![
+            2, 1, 191, 28, 0, 0, 254, 255, 255, 255, 255, 255, 255, 255, 190, 159, 170, 63,
+        ],
+    );
+}
+
+#[test]
+fn test_roundtrip_field_128_bit() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Id {
+        #[serde(with = "serde_net::le")]
+        value: u128,
+    }
+
+    test_roundtrip_ok(Id { value: 1 }, {
+        let mut output = vec![0; 16];
+        output[0] = 1;
+        output
+    });
+}
+
+#[test]
+fn test_roundtrip_field_big_endian() {
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Payload {
+        #[serde(with = "serde_net::be")]
+        id: u64,
+        count: i16,
+        #[serde(with = "serde_net::be")]
+        scale: f64,
+    }
+
+    let options = Options::new().with_endian(Endian::Little);
+    test_roundtrip_with_options_ok(
+        Payload {
+            id: 9764533,
+            count: -3456,
+            scale: -78.42568,
+        },
+        options,
+        vec![
+            0, 0, 0, 0, 0, 148, 254, 181, 128, 242, 192, 83, 155, 62, 87, 83, 163, 236,
+        ],
+    );
+}
+
+#[test]
+fn test_deserialize_bool_lenient() {
+    let mut input = vec![2];
+    let value: bool = from_bytes(&mut input).unwrap();
+    assert!(value);
+}
+
+#[test]
+fn test_deserialize_strict_bool() {
+    let options = Options::new().with_strict(true);
+    test_roundtrip_with_options_ok(true, options, vec![1]);
+    test_roundtrip_with_options_ok(false, options, vec![0]);
+
+    let mut input = vec![2];
+    let result: Result<bool, Error> = from_bytes_with_options(&mut input, options);
+    assert!(matches!(
+        result.unwrap_err().kind(),
+        ErrorKind::InvalidTag(2)
+    ));
+}
+
+#[test]
+fn test_deserialize_strict_option() {
+    let options = Options::new().with_strict(true);
+    test_roundtrip_with_options_ok(Some(7u8), options, vec![1, 7]);
+    test_roundtrip_with_options_ok(None::<u8>, options, vec![0]);
+
+    let mut input = vec![255, 7];
+    let result: Result<Option<u8>, Error> = from_bytes_with_options(&mut input, options);
+    assert!(matches!(
+        result.unwrap_err().kind(),
+        ErrorKind::InvalidTag(255)
+    ));
+}
+
+#[test]
+fn test_deserialize_str() {
+    let mut input = vec![0, 2, 97, 98];
+    let value: Box<str> = from_bytes(&mut input).unwrap();
+    assert_eq!(&*value, "ab");
+}
+
+#[test]
+fn test_deserialize_unsupported() {
+    let mut input = vec![0];
+    let result: Result<de::IgnoredAny, Error> = from_bytes(&mut input);
+    assert!(matches!(
+        result.unwrap_err().kind(),
+        ErrorKind::Unsupported("deserialize_ignored_any")
+    ));
+
+    let mut deserializer = Deserializer::from_bytes(vec![0, 2, 97, 98]);
+    let result = <&str as de::Deserialize>::deserialize(&mut deserializer);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_from_slice_borrowed() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Borrowed<'a> {
+        name: &'a str,
+        #[serde(borrow)]
+        label: Cow<'a, str>,
+        #[serde(borrow)]
+        payload: &'a Bytes,
+    }
+
+    let input = vec![0, 2, 97, 98, 0, 1, 99, 0, 3, 1, 2, 3];
+    let value: Borrowed = from_slice(&input).unwrap();
+    assert_eq!(value.name, "ab");
+    assert!(matches!(value.label, Cow::Borrowed("c")));
+    assert_eq!(value.payload, Bytes::new(&[1, 2, 3]));
+    assert_eq!(value.name.as_ptr(), input[2..].as_ptr());
+    assert_eq!(value.payload.as_ptr(), input[9..].as_ptr());
+}
+
+#[test]
+fn test_from_slice_owned() {
+    let input = [0, 2, 72, 105];
+    let value: String = from_slice(&input).unwrap();
+    assert_eq!(value, "Hi");
+
+    let result: Result<&str, Error> = from_slice(&input[..3]);
+    assert!(matches!(
+        result.unwrap_err().kind(),
+        ErrorKind::EofWhileDeserializing
+    ));
+
+    let result: Result<&str, Error> = from_slice(&[0, 1, 72, 105]);
+    assert!(matches!(
+        result.unwrap_err().kind(),
+        ErrorKind::TrailingBytes
+    ));
+
+    let result: Result<&str, Error> = from_slice(&[0, 1, 255]);
+    assert!(matches!(
+        result.unwrap_err().kind(),
+        ErrorKind::InvalidString
+    ));
+}
+
+#[test]
+fn test_error_offset() {
+    let mut value = vec![0, 4, 72, 101, 108];
+    let err = from_bytes::<_, String>(&mut value).unwrap_err();
+    assert_eq!(err.offset(), Some(2));
+    assert_eq!(err.to_string(), "EOF while deserializing at offset 2");
+
+    let err = from_reader::<_, String>(&[0, 4, 72, 101, 108][..]).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::EofWhileDeserializing));
+    assert_eq!(err.offset(), Some(2));
+
+    let err = from_slice::<String>(&[0, 2, 72, 101, 108]).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::TrailingBytes));
+    assert_eq!(err.offset(), Some(4));
+
+    let err = from_reader::<_, (u8, char)>(&[1, 0, 0xd8, 0, 0][..]).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidChar));
+    assert_eq!(err.offset(), Some(1));
+
+    let err = from_slice::<Vec<E>>(&[0, 2, 0, 9]).unwrap_err();
+    match err.kind() {
+        ErrorKind::Message(msg) => assert!(msg.as_str().starts_with("invalid value")),
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+    assert_eq!(err.offset(), Some(3));
+}
+
+#[test]
+fn test_serialize_error_has_no_offset() {
+    let err = to_vec(&Variant(256)).unwrap_err();
+    assert_eq!(err.offset(), None);
+}
+
+#[test]
+fn test_deserializer_position() {
+    let input = [0, 0, 0, 7, 0, 1, 97];
+    let mut deserializer = Deserializer::from_slice(&input);
+    assert_eq!(deserializer.position(), 0);
+    let value = u32::deserialize(&mut deserializer).unwrap();
+    assert_eq!(value, 7);
+    assert_eq!(deserializer.position(), 4);
+    let value = String::deserialize(&mut deserializer).unwrap();
+    assert_eq!(value, "a");
+    assert_eq!(deserializer.position(), 7);
+
+    let mut deserializer = Deserializer::from_reader(&input[..]);
+    u16::deserialize(&mut deserializer).unwrap();
+    assert_eq!(deserializer.position(), 2);
+}
+
+#[test]
+fn test_error_path() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Opt {
+        kind: u8,
+        value: char,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Header {
+        id: u16,
+        options: Vec<Opt>,
+    }
+
+    let mut input = vec![0, 1, 0, 4];
+    for _ in 0..3 {
+        input.extend_from_slice(&[0, 0, 0, 0, 97]);
+    }
+    input.extend_from_slice(&[0, 0, 0xd8, 0, 0]);
+    let err = from_slice::<Header>(&input).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidChar));
+    assert_eq!(
+        err.path(),
+        [
+            Segment::Type("Header"),
+            Segment::Field("options"),
+            Segment::Index(3),
+            Segment::Type("Opt"),
+            Segment::Field("value"),
+        ]
+    );
+    assert_eq!(
+        err.to_string(),
+        "Header.options[3].value: invalid char at offset 20"
+    );
+}
+
+#[test]
+fn test_error_path_enum_and_map() {
+    let err = from_slice::<E>(&[3, 0, 0]).unwrap_err();
+    assert_eq!(
+        err.path(),
+        [
+            Segment::Type("E"),
+            Segment::Variant("Struct"),
+            Segment::Field("a")
+        ]
+    );
+    assert_eq!(
+        err.to_string(),
+        "E::Struct.a: EOF while deserializing at offset 1"
+    );
+
+    let err = from_slice::<BTreeMap<String, u8>>(&[0, 2, 0, 1, 97, 1, 0, 1, 255]).unwrap_err();
+    assert_eq!(err.path(), [Segment::MapKey(1)]);
+    assert_eq!(err.to_string(), "[key 1]: invalid string at offset 6");
+
+    let err = from_slice::<BTreeMap<u8, (u8, char)>>(&[0, 1, 7, 1]).unwrap_err();
+    assert_eq!(err.path(), [Segment::MapValue(0), Segment::Index(1)]);
+}
+
+#[test]
+fn test_serialize_error_path() {
+    #[derive(Serialize)]
+    struct Packet {
+        id: u8,
+        kinds: Vec<Variant>,
+    }
+
+    let value = Packet {
+        id: 1,
+        kinds: vec![Variant(0), Variant(300)],
+    };
+    let err = to_vec(&value).unwrap_err();
+    assert_eq!(
+        err.path(),
+        [
+            Segment::Type("Packet"),
+            Segment::Field("kinds"),
+            Segment::Index(1)
+        ]
+    );
+    assert_eq!(
+        err.to_string(),
+        "Packet.kinds[1]: variant index 300 exceeds maximum of 255"
+    );
+}
+
+#[test]
+fn test_size_limit() {
+    let options = Options::new().with_limits(Limits::new().with_max_size(4));
+    let mut input = vec![0, 2, 97, 98];
+    let value: String = from_bytes_with_options(&mut input, options).unwrap();
+    assert_eq!(value, "ab");
+
+    let err = from_slice_with_options::<(u32, u8)>(&[0, 0, 0, 1, 2], options).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::SizeLimitExceeded { limit: 4 }
+    ));
+    assert_eq!(err.offset(), Some(4));
+
+    let err = from_reader_with_options::<_, String>(&[0, 3, 97, 98, 99][..], options).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::SizeLimitExceeded { limit: 4 }
+    ));
+}
+
+#[test]
+fn test_allocation_limit() {
+    let options = Options::new().with_limits(Limits::new().with_max_alloc(2));
+    let value: Vec<String> =
+        from_slice_with_options(&[0, 2, 0, 2, 97, 98, 0, 1, 99], options).unwrap();
+    assert_eq!(value, vec!["ab", "c"]);
+
+    let err = from_reader_with_options::<_, ByteBuf>(&[0xff, 0xff][..], options).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::AllocationLimitExceeded {
+            length: 65_535,
+            limit: 2
+        }
+    ));
+    assert_eq!(err.offset(), Some(2));
+}
+
+#[test]
+fn test_total_allocation_limit() {
+    let options = Options::new().with_limits(Limits::new().with_max_total_alloc(3));
+    let value: Vec<String> =
+        from_slice_with_options(&[0, 2, 0, 2, 97, 98, 0, 1, 99], options).unwrap();
+    assert_eq!(value, vec!["ab", "c"]);
+
+    let err = from_slice_with_options::<Vec<String>>(&[0, 2, 0, 2, 97, 98, 0, 2, 99, 100], options)
+        .unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::TotalAllocationLimitExceeded { limit: 3 }
+    ));
+    assert_eq!(err.path(), [Segment::Index(1)]);
+}
+
+#[test]
+fn test_depth_limit() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum List {
+        Nil,
+        Cons(u8, Box<List>),
     }
+
+    let options = Options::new().with_limits(Limits::new().with_max_depth(6));
+    let value = List::Cons(1, Box::new(List::Cons(2, Box::new(List::Nil))));
+    test_roundtrip_with_options_ok(value, options, vec![1, 1, 1, 2, 0]);
+
+    let mut input = vec![1, 1, 1, 2, 1, 3, 0];
+    let err = from_bytes_with_options::<_, List>(&mut input, options).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::DepthLimitExceeded { limit: 6 }
+    ));
+
+    let mut input = vec![1; 100_000];
+    let err = from_bytes_with_options::<_, List>(&mut input, options).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::DepthLimitExceeded { limit: 6 }
+    ));
+
+    let options = Options::new().with_limits(Limits::new().with_max_depth(4));
+    let err = from_slice_with_options::<Vec<Vec<Vec<Vec<Vec<u8>>>>>>(
+        &[0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0],
+        options,
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::DepthLimitExceeded { limit: 4 }
+    ));
+
+    let err = from_slice::<List>(&vec![1; 2_000_000]).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::DepthLimitExceeded {
+            limit: Limits::DEFAULT_MAX_DEPTH
+        }
+    ));
+
+    let options = Options::new().with_limits(Limits::new().without_max_depth());
+    let mut input = vec![1; 2 * Limits::DEFAULT_MAX_DEPTH];
+    input.push(0);
+    assert!(from_bytes::<_, List>(&mut input.clone()).is_err());
+    assert!(from_bytes_with_options::<_, List>(&mut input, options).is_ok());
+}
+
+#[test]
+fn test_serializer_lifecycle() {
+    let mut serializer = Serializer::new(Vec::new());
+    1u16.serialize(&mut serializer).unwrap();
+    assert_eq!(serializer.bytes_written(), 2);
+    "ab".serialize(&mut serializer).unwrap();
+    assert_eq!(serializer.bytes_written(), 6);
+    assert_eq!(serializer.into_inner(), vec![0, 1, 0, 2, b'a', b'b']);
+
+    let options = Options::new().with_endian(Endian::Little);
+    let mut serializer = Serializer::with_options(Vec::new(), options);
+    1u32.serialize(&mut serializer).unwrap();
+    assert_eq!(serializer.into_inner(), vec![1, 0, 0, 0]);
+}
+
+#[test]
+fn test_deserializer_lifecycle() {
+    let input = [0, 1, 0, 2, b'a', b'b', 0xff];
+    let mut deserializer = Deserializer::from_reader(&input[..]);
+    assert_eq!(u16::deserialize(&mut deserializer).unwrap(), 1);
+    assert_eq!(deserializer.position(), 2);
+    assert_eq!(String::deserialize(&mut deserializer).unwrap(), "ab");
+    assert_eq!(deserializer.position(), 6);
+    assert!(matches!(
+        deserializer.end().unwrap_err().kind(),
+        ErrorKind::TrailingBytes
+    ));
+    let rest = deserializer.into_inner().into_inner();
+    assert!(rest.is_empty());
+
+    let mut deserializer = Deserializer::from_slice(&input);
+    assert_eq!(
+        <(u16, &str)>::deserialize(&mut deserializer).unwrap(),
+        (1, "ab")
+    );
+    assert_eq!(deserializer.into_inner().remaining(), [0xff]);
+
+    let mut deserializer = Deserializer::from_slice(&input[..6]);
+    <(u16, &str)>::deserialize(&mut deserializer).unwrap();
+    deserializer.end().unwrap();
+}
+
+#[test]
+fn test_stream_deserializer() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message {
+        id: u16,
+        body: String,
+    }
+
+    let messages = vec![
+        Message {
+            id: 1,
+            body: "a".to_owned(),
+        },
+        Message {
+            id: 2,
+            body: String::new(),
+        },
+        Message {
+            id: 3,
+            body: "ccc".to_owned(),
+        },
+    ];
+    let mut serializer = Serializer::new(Vec::new());
+    for message in &messages {
+        message.serialize(&mut serializer).unwrap();
+    }
+    let input = serializer.into_inner();
+
+    let stream = StreamDeserializer::new(Deserializer::from_reader(input.as_slice()));
+    let decoded = stream.collect::<Result<Vec<Message>, _>>().unwrap();
+    assert_eq!(decoded, messages);
+
+    let stream = StreamDeserializer::new(Deserializer::from_slice(&input));
+    let decoded = stream.collect::<Result<Vec<Message>, _>>().unwrap();
+    assert_eq!(decoded, messages);
+
+    let mut stream = StreamDeserializer::<_, u16>::new(Deserializer::from_reader(&[] as &[u8]));
+    assert!(stream.next().is_none());
+}
+
+#[test]
+fn test_stream_deserializer_truncated() {
+    let input = [0, 1, 0, 2, 0];
+    let mut stream = StreamDeserializer::<_, u16>::new(Deserializer::from_reader(&input[..]));
+    assert_eq!(stream.next().unwrap().unwrap(), 1);
+    assert_eq!(stream.next().unwrap().unwrap(), 2);
+    let err = stream.next().unwrap().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::EofWhileDeserializing));
+    assert_eq!(err.offset(), Some(4));
+    assert!(stream.next().is_none());
+
+    let mut stream = StreamDeserializer::<_, &str>::new(Deserializer::from_slice(&[0, 3, b'a']));
+    let err = stream.next().unwrap().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::EofWhileDeserializing));
+}
+
+#[test]
+fn test_take_from_bytes() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Header {
+        kind: u8,
+        length: u16,
+    }
+
+    let input = [1, 0, 3, 0xaa, 0xbb, 0xcc];
+    let (header, rest): (Header, _) = take_from_bytes(&input).unwrap();
+    assert_eq!(header, Header { kind: 1, length: 3 });
+    assert_eq!(rest, [0xaa, 0xbb, 0xcc]);
+
+    let (value, rest): (u16, _) = take_from_bytes(&input[..2]).unwrap();
+    assert_eq!(value, 0x0100);
+    assert!(rest.is_empty());
+
+    let options = Options::new().with_endian(Endian::Little);
+    let (value, rest): (u16, _) = take_from_bytes_with_options(&input, options).unwrap();
+    assert_eq!(value, 1);
+    assert_eq!(rest, [3, 0xaa, 0xbb, 0xcc]);
+
+    let err = take_from_bytes::<Header>(&input[..2]).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::EofWhileDeserializing));
+}
+
+#[test]
+fn test_take_from_slice() {
+    let input = [0, 2, b'h', b'i', 0xff];
+    let (value, rest): (&str, _) = take_from_slice(&input).unwrap();
+    assert_eq!(value, "hi");
+    assert_eq!(rest, [0xff]);
+    assert!(std::ptr::eq(value.as_ptr(), &input[2]));
+}
+
+#[test]
+fn test_serialized_size() {
+    #[derive(Serialize)]
+    struct Message {
+        id: u32,
+        name: String,
+        tags: Vec<u16>,
+        kind: E,
+        extra: Option<u8>,
+    }
+
+    let value = Message {
+        id: 7,
+        name: "abc".to_owned(),
+        tags: vec![1, 2],
+        kind: E::Tuple(1, 2),
+        extra: Some(2),
+    };
+    assert_eq!(
+        serialized_size(&value).unwrap(),
+        to_vec(&value).unwrap().len() as u64
+    );
+
+    let options = Options::new()
+        .with_length_prefix(LengthPrefix::U32)
+        .with_discriminant(Discriminant::Varint);
+    assert_eq!(
+        serialized_size_with_options(&value, options).unwrap(),
+        to_vec_with_options(&value, options).unwrap().len() as u64
+    );
+
+    let options = Options::new().with_length_prefix(LengthPrefix::U8);
+    let err = serialized_size_with_options(&vec![0u8; 256], options).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::LengthOverflow {
+            length: 256,
+            max: 255
+        }
+    ));
+}
+
+#[test]
+fn test_to_slice() {
+    let mut buf = [0; 8];
+    let written = to_slice(&(1u16, "ab"), &mut buf).unwrap();
+    assert_eq!(written, [0, 1, 0, 2, b'a', b'b']);
+
+    let options = Options::new().with_endian(Endian::Little);
+    let written = to_slice_with_options(&1u32, &mut buf, options).unwrap();
+    assert_eq!(written, [1, 0, 0, 0]);
+
+    let mut buf = [0; 4];
+    let written = to_slice(&1u32, &mut buf).unwrap();
+    assert_eq!(written.len(), 4);
+
+    let err = to_slice(&(1u16, "ab"), &mut buf).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::BufferTooSmall));
+    let err = to_slice(&1u8, &mut []).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::BufferTooSmall));
+}
+
+#[test]
+fn test_roundtrip_ipv4_addr() {
+    test_roundtrip_ok(Ipv4Addr::new(192, 168, 0, 1), vec![192, 168, 0, 1]);
+}
+
+#[test]
+fn test_roundtrip_ipv6_addr() {
+    test_roundtrip_ok(
+        Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1),
+        vec![0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+    );
+}
+
+#[test]
+fn test_roundtrip_ip_addr() {
+    test_roundtrip_ok(IpAddr::V4(Ipv4Addr::LOCALHOST), vec![0, 127, 0, 0, 1]);
+    test_roundtrip_ok(
+        IpAddr::V6(Ipv6Addr::LOCALHOST),
+        vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+    );
+}
+
+#[test]
+fn test_roundtrip_socket_addr_v4() {
+    test_roundtrip_ok(
+        SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 443),
+        vec![10, 0, 0, 1, 0x01, 0xbb],
+    );
+}
+
+#[test]
+fn test_roundtrip_socket_addr_v6() {
+    test_roundtrip_ok(
+        SocketAddrV6::new(Ipv6Addr::LOCALHOST, 8080, 0, 0),
+        vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0x1f, 0x90],
+    );
+}
+
+#[test]
+fn test_roundtrip_socket_addr() {
+    test_roundtrip_ok(
+        SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 443)),
+        vec![0, 10, 0, 0, 1, 0x01, 0xbb],
+    );
+    test_roundtrip_ok(
+        SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::LOCALHOST, 8080, 0, 0)),
+        vec![
+            1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0x1f, 0x90,
+        ],
+    );
+}
+
+#[test]
+fn test_roundtrip_socket_addr_little_endian() {
+    let options = Options::new().with_endian(Endian::Little);
+    test_roundtrip_with_options_ok(
+        SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 443),
+        options,
+        vec![10, 0, 0, 1, 0xbb, 0x01],
+    );
+}
+
+#[test]
+fn test_roundtrip_human_readable() {
+    let options = Options::new().with_human_readable(true);
+    test_roundtrip_with_options_ok(
+        Ipv4Addr::new(10, 0, 0, 1),
+        options,
+        vec![0, 8, b'1', b'0', b'.', b'0', b'.', b'0', b'.', b'1'],
+    );
+    test_roundtrip_with_options_ok(
+        IpAddr::V6(Ipv6Addr::LOCALHOST),
+        options,
+        vec![0, 3, b':', b':', b'1'],
+    );
+}
+
+#[test]
+fn test_roundtrip_varint_integers() {
+    let options = Options::new().with_int_encoding(IntEncoding::Varint);
+    test_roundtrip_with_options_ok(0u32, options, vec![0]);
+    test_roundtrip_with_options_ok(127u16, options, vec![0x7f]);
+    test_roundtrip_with_options_ok(300u16, options, vec![0xac, 0x02]);
+    test_roundtrip_with_options_ok(
+        u64::MAX,
+        options,
+        vec![0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+    );
+    test_roundtrip_with_options_ok(u128::MAX, options, {
+        let mut output = vec![0xff; 18];
+        output.push(0x03);
+        output
+    });
+    test_roundtrip_with_options_ok(0i32, options, vec![0]);
+    test_roundtrip_with_options_ok(-1i32, options, vec![1]);
+    test_roundtrip_with_options_ok(1i32, options, vec![2]);
+    test_roundtrip_with_options_ok(-64i16, options, vec![0x7f]);
+    test_roundtrip_with_options_ok(64i16, options, vec![0x80, 0x01]);
+    test_roundtrip_with_options_ok(i16::MIN, options, vec![0xff, 0xff, 0x03]);
+    test_roundtrip_with_options_ok(
+        i64::MIN,
+        options,
+        vec![0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+    );
+    test_roundtrip_with_options_ok(i128::MIN, options, {
+        let mut output = vec![0xff; 18];
+        output.push(0x03);
+        output
+    });
+    test_roundtrip_with_options_ok(255u8, options, vec![255]);
+    test_roundtrip_with_options_ok(-1i8, options, vec![255]);
+    test_roundtrip_with_options_ok(1.0f32, options, vec![63, 128, 0, 0]);
+}
+
+#[test]
+fn test_roundtrip_varint_length_prefix() {
+    let options = Options::new().with_length_prefix(LengthPrefix::Varint);
+    test_roundtrip_with_options_ok("abc".to_owned(), options, vec![3, b'a', b'b', b'c']);
+
+    let mut output = vec![0xc8, 0x01];
+    output.extend(vec![7; 200]);
+    test_roundtrip_with_options_ok(vec![7u8; 200], options, output);
+
+    let options = options
+        .with_int_encoding(IntEncoding::Varint)
+        .with_discriminant(Discriminant::Varint);
+    test_roundtrip_with_options_ok(
+        (E::Newtype(300), vec![-1i64, 1]),
+        options,
+        vec![1, 0xac, 0x02, 2, 1, 2],
+    );
+}
+
+#[test]
+fn test_deserialize_varint_overflow() {
+    let options = Options::new().with_int_encoding(IntEncoding::Varint);
+    let err = from_slice_with_options::<u16>(&[0xff, 0xff, 0x04], options).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::VarintOverflow));
+    assert_eq!(err.offset(), Some(0));
+
+    let err = from_slice_with_options::<u16>(&[0x80, 0x80, 0x80, 0x00], options).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::VarintOverflow));
+
+    let err = from_slice_with_options::<(u8, i32)>(&[1, 0xff, 0xff, 0xff, 0xff, 0x10], options)
+        .unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::VarintOverflow));
+    assert_eq!(err.offset(), Some(1));
+
+    let options = Options::new().with_length_prefix(LengthPrefix::Varint);
+    let input = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02];
+    let err = from_slice_with_options::<&[u8]>(&input, options).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::VarintOverflow));
+}
+
+#[test]
+fn test_deserialize_varint_overlong() {
+    let options = Options::new().with_int_encoding(IntEncoding::Varint);
+    let err = from_slice_with_options::<u32>(&[0x80, 0x00], options).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::VarintOverlong));
+    assert_eq!(err.offset(), Some(0));
+
+    let err = from_slice_with_options::<i64>(&[0x81, 0x80, 0x00], options).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::VarintOverlong));
+
+    let options = Options::new().with_discriminant(Discriminant::Varint);
+    let err = from_slice_with_options::<E>(&[0x80, 0x00], options).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::VarintOverlong));
+}
+
+#[test]
+fn test_roundtrip_quic_varint() {
+    let value = |v| QuicVarInt::new(v).unwrap();
+    test_roundtrip_ok(value(37), vec![0x25]);
+    test_roundtrip_ok(value(63), vec![0x3f]);
+    test_roundtrip_ok(value(64), vec![0x40, 0x40]);
+    test_roundtrip_ok(value(15_293), vec![0x7b, 0xbd]);
+    test_roundtrip_ok(value(494_878_333), vec![0x9d, 0x7f, 0x3e, 0x7d]);
+    test_roundtrip_ok(
+        value(151_288_809_941_952_652),
+        vec![0xc2, 0x19, 0x7c, 0x5e, 0xff, 0x14, 0xe8, 0x8c],
+    );
+    test_roundtrip_ok(
+        QuicVarInt::MAX,
+        vec![0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+    );
+
+    let options = Options::new().with_endian(Endian::Little);
+    test_roundtrip_with_options_ok(value(15_293), options, vec![0x7b, 0xbd]);
+}
+
+#[test]
+fn test_deserialize_quic_varint_non_minimal() {
+    let result: QuicVarInt = from_slice(&[0x40, 0x25]).unwrap();
+    assert_eq!(result.into_inner(), 37);
+    let result: QuicVarInt = from_slice(&[0x80, 0, 0, 0x25]).unwrap();
+    assert_eq!(result.into_inner(), 37);
+
+    let err = from_slice::<QuicVarInt>(&[0x80, 0, 0]).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::EofWhileDeserializing));
+}
+
+#[test]
+fn test_quic_varint_range() {
+    assert!(QuicVarInt::new(1 << 62).is_none());
+    let err = QuicVarInt::try_from(u64::MAX).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::QuicVarIntOverflow { value: u64::MAX }
+    ));
+    assert_eq!(u64::from(QuicVarInt::from(u32::MAX)), u64::from(u32::MAX));
+}
+
+#[test]
+fn test_roundtrip_quic_varint_length_prefix() {
+    let options = Options::new().with_length_prefix(LengthPrefix::QuicVarInt);
+    test_roundtrip_with_options_ok("abc".to_owned(), options, vec![3, b'a', b'b', b'c']);
+
+    let mut output = vec![0x40, 0x64];
+    output.extend(vec![7; 100]);
+    test_roundtrip_with_options_ok(vec![7u8; 100], options, output);
+
+    let result: Vec<u8> = from_slice_with_options(&[0x80, 0, 0, 1, 9], options).unwrap();
+    assert_eq!(result, [9]);
+}
+
+#[test]
+fn test_roundtrip_u24() {
+    test_roundtrip_ok(U24::new(0x01_0203).unwrap(), vec![1, 2, 3]);
+    test_roundtrip_ok(U24::MIN, vec![0, 0, 0]);
+    test_roundtrip_ok(U24::MAX, vec![255, 255, 255]);
+
+    let options = Options::new().with_endian(Endian::Little);
+    test_roundtrip_with_options_ok(U24::new(0x01_0203).unwrap(), options, vec![1, 2, 3]);
+}
+
+#[test]
+fn test_roundtrip_i24() {
+    test_roundtrip_ok(I24::new(-2).unwrap(), vec![255, 255, 254]);
+    test_roundtrip_ok(I24::new(7359).unwrap(), vec![0, 28, 191]);
+    test_roundtrip_ok(I24::MIN, vec![128, 0, 0]);
+    test_roundtrip_ok(I24::MAX, vec![127, 255, 255]);
+}
+
+#[test]
+fn test_roundtrip_u48() {
+    test_roundtrip_ok(U48::new(0x0102_0304_0506).unwrap(), vec![1, 2, 3, 4, 5, 6]);
+    test_roundtrip_ok(U48::MAX, vec![255; 6]);
+}
+
+#[test]
+fn test_odd_int_range() {
+    assert!(U24::new(1 << 24).is_none());
+    assert!(I24::new(-(1 << 23) - 1).is_none());
+    assert!(I24::new(1 << 23).is_none());
+    assert!(U48::new(1 << 48).is_none());
+
+    let err = U24::try_from(0x100_0000).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::IntegerOverflow {
+            value: 0x100_0000,
+            ty: "u24"
+        }
+    ));
+    assert_eq!(err.to_string(), "16777216 is out of range for u24");
+    assert_eq!(u32::from(U24::try_from(5).unwrap()), 5);
+}
+
+#[test]
+fn test_roundtrip_odd_int_fields() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Handshake {
+        kind: u8,
+        #[serde(with = "serde_net::u24")]
+        length: u32,
+        #[serde(with = "serde_net::i24")]
+        delta: i32,
+        #[serde(with = "serde_net::u48")]
+        address: u64,
+        #[serde(with = "serde_net::le")]
+        tail: U24,
+    }
+
+    test_roundtrip_ok(
+        Handshake {
+            kind: 1,
+            length: 0x00_0102,
+            delta: -1,
+            address: 0x0011_2233_4455,
+            tail: U24::new(0x01_0203).unwrap(),
+        },
+        vec![
+            1, 0, 1, 2, 255, 255, 255, 0, 0x11, 0x22, 0x33, 0x44, 0x55, 3, 2, 1,
+        ],
+    );
+
+    let value = Handshake {
+        kind: 1,
+        length: 0x100_0000,
+        delta: 0,
+        address: 0,
+        tail: U24::MIN,
+    };
+    let err = to_vec(&value).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::Message(_)));
+    assert_eq!(
+        err.to_string(),
+        "Handshake.length: 16777216 is out of range for u24"
+    );
+}
+
+serde_net::bitfield! {
+    #[derive(Debug, PartialEq)]
+    struct Ipv4Prefix {
+        version: u8 = 4,
+        ihl: u8 = 4,
+        dscp: u8 = 6,
+        ecn: u8 = 2,
+        total_length: u16 = 16,
+        identification: u16 = 16,
+        flags: u8 = 3,
+        fragment_offset: u16 = 13,
+    }
+}
+
+#[test]
+fn test_roundtrip_bitfield() {
+    assert_eq!(Ipv4Prefix::BITS, 64);
+    test_roundtrip_ok(
+        Ipv4Prefix {
+            version: 4,
+            ihl: 5,
+            dscp: 46,
+            ecn: 1,
+            total_length: 0x0054,
+            identification: 0xabcd,
+            flags: 0b010,
+            fragment_offset: 0x1234,
+        },
+        vec![0x45, 0xb9, 0x00, 0x54, 0xab, 0xcd, 0x52, 0x34],
+    );
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Packet {
+        header: Ipv4Prefix,
+        ttl: u8,
+    }
+
+    let options = Options::new().with_endian(Endian::Little);
+    test_roundtrip_with_options_ok(
+        Packet {
+            header: Ipv4Prefix {
+                version: 15,
+                ihl: 0,
+                dscp: 0,
+                ecn: 3,
+                total_length: 0xffff,
+                identification: 0,
+                flags: 0b111,
+                fragment_offset: 0,
+            },
+            ttl: 64,
+        },
+        options,
+        vec![0xf0, 0x03, 0xff, 0xff, 0, 0, 0xe0, 0, 64],
+    );
+}
+
+#[test]
+fn test_bitfield_overflow() {
+    let value = Ipv4Prefix {
+        version: 16,
+        ihl: 5,
+        dscp: 0,
+        ecn: 0,
+        total_length: 0,
+        identification: 0,
+        flags: 0,
+        fragment_offset: 0,
+    };
+    let err = to_vec(&value).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::Message(_)));
+    assert_eq!(
+        err.to_string(),
+        "Ipv4Prefix.version: value 16 does not fit in 4 bits"
+    );
+
+    let err = from_slice::<Ipv4Prefix>(&[0x45, 0, 0]).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::EofWhileDeserializing));
+}
+
+#[test]
+fn test_bitfield_u128() {
+    serde_net::bitfield! {
+        #[derive(Debug, PartialEq)]
+        struct Narrow {
+            a: u128 = 56,
+            b: u8 = 8,
+        }
+    }
+
+    serde_net::bitfield! {
+        #[derive(Debug, PartialEq)]
+        struct Wide {
+            a: u128 = 80,
+            b: u8 = 8,
+        }
+    }
+
+    let err = to_vec(&Narrow { a: 1 << 70, b: 1 }).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Narrow.a: value 1180591620717411303424 does not fit in 56 bits"
+    );
+
+    let mut output = vec![0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0x01, 0x2a];
+    test_roundtrip_ok(
+        Wide {
+            a: (1 << 79) | 1,
+            b: 42,
+        },
+        output.clone(),
+    );
+
+    let err = to_vec(&Wide { a: 1 << 80, b: 0 }).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Wide.a: value 1208925819614629174706176 does not fit in 80 bits"
+    );
+
+    output[0] = 0;
+    assert_eq!(from_slice::<Wide>(&output).unwrap(), Wide { a: 1, b: 42 });
+}
+
+#[test]
+fn test_bits_roundtrip() {
+    let options = BitOptions::new().with_length_bits(4);
+    let value = (true, vec![1u8, 2]);
+    let bytes = bits::to_vec(&value, options).unwrap();
+    assert_eq!(bytes, [0x90, 0x08, 0x10]);
+    assert_eq!(
+        bits::from_slice::<(bool, Vec<u8>)>(&bytes, options).unwrap(),
+        value
+    );
+
+    let options = options.with_bit_order(BitOrder::LsbFirst);
+    let bytes = bits::to_vec(&value, options).unwrap();
+    assert_eq!(bytes, [0x25, 0x40, 0x00]);
+    assert_eq!(
+        bits::from_slice::<(bool, Vec<u8>)>(&bytes, options).unwrap(),
+        value
+    );
+
+    let options = BitOptions::new().with_discriminant_bits(2);
+    let bytes = bits::to_vec(&E::Tuple(1, 2), options).unwrap();
+    assert_eq!(bytes, [0x80, 0x40, 0x80]);
+    assert_eq!(
+        bits::from_slice::<E>(&bytes, options).unwrap(),
+        E::Tuple(1, 2)
+    );
+
+    for value in [E::Unit, E::Newtype(7), E::Struct { a: u32::MAX }] {
+        let bytes = bits::to_vec(&value, options).unwrap();
+        assert_eq!(bits::from_slice::<E>(&bytes, options).unwrap(), value);
+    }
+
+    let options = BitOptions::new().with_char_bits(21);
+    let value = (false, 'é', String::from("hé"), Some(-3i64), u128::MAX - 1);
+    let bytes = bits::to_vec(&value, options).unwrap();
+    assert_eq!(
+        bits::from_slice::<(bool, char, String, Option<i64>, u128)>(&bytes, options).unwrap(),
+        value
+    );
+}
+
+#[test]
+fn test_bits_borrowed() {
+    let options = BitOptions::new().with_bool_bits(8);
+    let bytes = bits::to_vec(&(true, "hi"), options).unwrap();
+    assert_eq!(bytes, [1, 0, 2, b'h', b'i']);
+    assert_eq!(
+        bits::from_slice::<(bool, &str)>(&bytes, options).unwrap(),
+        (true, "hi")
+    );
+}
+
+#[test]
+fn test_bits_padding() {
+    let options = BitOptions::new().with_padding(Padding::Ones);
+    assert_eq!(bits::to_vec(&(true, false), options).unwrap(), [0xbf]);
+    assert_eq!(
+        bits::from_slice::<(bool, bool)>(&[0xbf], options).unwrap(),
+        (true, false)
+    );
+    let err = bits::from_slice::<(bool, bool)>(&[0x80], options).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidPadding));
+
+    let options = options.with_padding(Padding::Zeros);
+    let err = bits::from_slice::<(bool, bool)>(&[0x81], options).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidPadding));
+    assert_eq!(err.offset(), Some(0));
+
+    let options = options.with_padding(Padding::Reject);
+    let err = bits::to_vec(&true, options).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::UnalignedEnd));
+    let err = bits::from_slice::<bool>(&[0x80], options).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::UnalignedEnd));
+    assert_eq!(bits::to_vec(&(1u8, 2u16), options).unwrap(), [1, 0, 2]);
+}
+
+#[test]
+fn test_bits_errors() {
+    #[derive(Debug, Deserialize)]
+    struct S {
+        _a: u8,
+        _b: u16,
+    }
+
+    let options = BitOptions::new();
+    let err = bits::from_slice::<S>(&[1, 2], options).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::EofWhileDeserializing));
+    assert_eq!(err.offset(), Some(2));
+    assert_eq!(err.path(), [Segment::Type("S"), Segment::Field("_b")]);
+
+    let err = bits::from_slice::<u8>(&[1, 2], options).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::TrailingBytes));
+
+    let err = bits::from_slice::<bool>(&[0x80], options.with_bool_bits(2)).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidTag(2)));
+
+    let options = options.with_length_bits(2);
+    let err = bits::to_vec(&[0u8; 4][..], options).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::LengthOverflow { length: 4, max: 3 }
+    ));
+
+    let err = bits::from_slice::<Vec<u8>>(&[0xc0], options).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::EofWhileDeserializing));
+
+    let err = bits::to_vec(&'é', options.with_char_bits(7)).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidChar));
+
+    let options = BitOptions::new().with_bool_bits(0);
+    let err = bits::to_vec(&(true, 5u8), options).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::InvalidBitWidth { bits: 0, max: 8 }
+    ));
+    let err = bits::from_slice::<(bool, u8)>(&[5], options).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::InvalidBitWidth { bits: 0, max: 8 }
+    ));
+
+    let options = BitOptions::new().with_length_bits(65);
+    let err = bits::to_vec("", options).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::InvalidBitWidth { bits: 65, max: 64 }
+    ));
+}
+
+#[test]
+fn test_bits_integer_widths() {
+    let options = BitOptions::new().with_u16_bits(12).with_i8_bits(4);
+    let value = (0xabcu16, -3i8);
+    let bytes = bits::to_vec(&value, options).unwrap();
+    assert_eq!(bytes, [0xab, 0xcd]);
+    assert_eq!(
+        bits::from_slice::<(u16, i8)>(&bytes, options).unwrap(),
+        value
+    );
+
+    let options = options.with_bit_order(BitOrder::LsbFirst);
+    let bytes = bits::to_vec(&value, options).unwrap();
+    assert_eq!(bytes, [0xbc, 0xda]);
+    assert_eq!(
+        bits::from_slice::<(u16, i8)>(&bytes, options).unwrap(),
+        value
+    );
+
+    for value in [-8i8, -1, 7] {
+        let bytes = bits::to_vec(&value, options).unwrap();
+        assert_eq!(bits::from_slice::<i8>(&bytes, options).unwrap(), value);
+    }
+
+    let err = bits::to_vec(&0x1000u16, options).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::IntegerWidthOverflow {
+            ty: "u16",
+            bits: 12
+        }
+    ));
+    let err = bits::to_vec(&8i8, options).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::IntegerWidthOverflow { ty: "i8", bits: 4 }
+    ));
+
+    let options = BitOptions::new().with_u128_bits(100).with_i128_bits(100);
+    let value = ((1u128 << 99) | 1, -(1i128 << 99));
+    let bytes = bits::to_vec(&value, options).unwrap();
+    assert_eq!(bytes.len(), 25);
+    assert_eq!(bytes[0], 0x80);
+    assert_eq!(
+        bits::from_slice::<(u128, i128)>(&bytes, options).unwrap(),
+        value
+    );
+
+    let err = bits::to_vec(&1u32, BitOptions::new().with_u32_bits(33)).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::InvalidBitWidth { bits: 33, max: 32 }
+    ));
+}
+
+#[test]
+fn test_bits_limits() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    enum Rec {
+        Leaf,
+        Node(Box<Rec>),
+    }
+
+    let options = BitOptions::new().with_discriminant_bits(1);
+    let err = bits::from_slice::<Rec>(&[0xff; 200_000], options).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::DepthLimitExceeded {
+            limit: Limits::DEFAULT_MAX_DEPTH
+        }
+    ));
+    assert_eq!(err.offset(), Some(16));
+
+    let options = options.with_limits(Limits::new().with_max_depth(4));
+    assert_eq!(
+        bits::from_slice::<Rec>(&[0xe0], options).unwrap(),
+        Rec::Node(Box::new(Rec::Node(Box::new(Rec::Node(Box::new(
+            Rec::Leaf
+        ))))))
+    );
+    let err = bits::from_slice::<Rec>(&[0xf0], options).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::DepthLimitExceeded { limit: 4 }
+    ));
+
+    let options = BitOptions::new().with_limits(Limits::new().with_max_alloc(2));
+    let err = bits::from_slice::<String>(&[0, 3, b'a', b'b', b'c'], options).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::AllocationLimitExceeded {
+            length: 3,
+            limit: 2
+        }
+    ));
+
+    let options = BitOptions::new().with_limits(Limits::new().with_max_size(2));
+    let err = bits::from_slice::<(u8, u8, u8)>(&[1, 2, 3], options).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::SizeLimitExceeded { limit: 2 }
+    ));
+}
+
+#[cfg(feature = "bitflags")]
+bitflags::bitflags! {
+    #[derive(Debug, PartialEq)]
+    struct Permissions: u16 {
+        const READ = 0x0004;
+        const WRITE = 0x0002;
+        const EXECUTE = 0x0001;
+    }
+}
+
+#[cfg(feature = "bitflags")]
+#[test]
+fn test_bitflags() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Entry {
+        #[serde(with = "serde_net::flags::reject")]
+        rejected: Permissions,
+        #[serde(with = "serde_net::flags::truncate")]
+        truncated: Permissions,
+        #[serde(with = "serde_net::flags::retain")]
+        retained: Permissions,
+    }
+
+    let value = Entry {
+        rejected: Permissions::READ | Permissions::WRITE,
+        truncated: Permissions::EXECUTE,
+        retained: Permissions::from_bits_retain(0x8001),
+    };
+    test_roundtrip_ok(value, vec![0, 6, 0, 1, 0x80, 1]);
+
+    let options = Options::new().with_endian(Endian::Little);
+    let value: Entry = from_slice_with_options(&[6, 0, 0x11, 0x80, 0x10, 0], options).unwrap();
+    assert_eq!(
+        value,
+        Entry {
+            rejected: Permissions::READ | Permissions::WRITE,
+            truncated: Permissions::EXECUTE,
+            retained: Permissions::from_bits_retain(0x0010),
+        }
+    );
+
+    let err = from_slice::<Entry>(&[0x01, 0x04, 0, 0, 0, 0]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Entry.rejected: unknown flag bits 0x100 at offset 0"
+    );
 }
@@ -0,0 +1,50 @@
+//! Builds under every feature set, so that serializing into a `SliceWrite`
+//! can be checked with and without `std` and `alloc`.
+
+use serde::{Serialize, Serializer};
+use serde_net::bits::{self, BitOptions};
+use serde_net::write::SliceWrite;
+use serde_net::{to_writer, ErrorKind};
+
+struct Shown(u32);
+
+impl Serialize for Shown {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(&self.0)
+    }
+}
+
+#[test]
+fn test_to_writer_slice_write_by_reference() {
+    let mut buf = [0; 4];
+    let mut writer = SliceWrite::new(&mut buf);
+    to_writer(&mut writer, &1u16).unwrap();
+    to_writer(&mut writer, &2u8).unwrap();
+    assert_eq!(writer.into_written(), [0, 1, 2]);
+
+    let mut buf = [0; 1];
+    let mut writer = SliceWrite::new(&mut buf);
+    let err = to_writer(&mut writer, &1u16).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::BufferTooSmall));
+}
+
+#[test]
+fn test_collect_str() {
+    let mut buf = [0; 8];
+    let mut writer = SliceWrite::new(&mut buf);
+    to_writer(&mut writer, &Shown(1234)).unwrap();
+    assert_eq!(writer.into_written(), [0, 4, b'1', b'2', b'3', b'4']);
+
+    let mut buf = [0; 8];
+    let mut writer = SliceWrite::new(&mut buf);
+    bits::to_writer(&mut writer, &(true, Shown(56)), BitOptions::new()).unwrap();
+    assert_eq!(writer.into_written(), [0x80, 0x01, 0x1a, 0x9b, 0]);
+
+    let mut buf = [0; 3];
+    let mut writer = SliceWrite::new(&mut buf);
+    let err = to_writer(&mut writer, &Shown(1234)).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::BufferTooSmall));
+}